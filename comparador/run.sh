@@ -1,8 +1,7 @@
 #!/bin/sh
 
 clear
-rm -fr logs/*.log
 rm -fr temp/*
 cargo b --release;
-./target/release/comparador -d datasets/open-images-dataset-v7/test_challenge_2018
+./target/release/comparador run -d datasets/open-images-dataset-v7/test_challenge_2018 --resume
     # 2> logs.log
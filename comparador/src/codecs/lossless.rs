@@ -0,0 +1,135 @@
+//! Bit-exact verification of lossless round-trips.
+use core::fmt;
+
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel};
+use num_traits::AsPrimitive;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LosslessMismatch {
+    Dimensions {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    Pixel {
+        x: u32,
+        y: u32,
+        expected: Vec<f64>,
+        found: Vec<f64>,
+    },
+}
+
+impl fmt::Display for LosslessMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LosslessMismatch::Dimensions { expected, found } => write!(
+                f,
+                "dimensions differ: expected {}x{}, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            LosslessMismatch::Pixel {
+                x,
+                y,
+                expected,
+                found,
+            } => write!(
+                f,
+                "first mismatching pixel at ({x}, {y}): expected {expected:?}, found {found:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LosslessMismatch {}
+
+/// Whether `color` holds at least the channels and the precision of `other`.
+fn covers(color: ColorType, other: ColorType) -> bool {
+    color.channel_count() >= other.channel_count()
+        && color.has_color() >= other.has_color()
+        && color.has_alpha() >= other.has_alpha()
+        && color.bytes_per_pixel() / color.channel_count()
+            >= other.bytes_per_pixel() / other.channel_count()
+}
+
+fn to_color(image: &DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => image.to_luma8().into(),
+        ColorType::La8 => image.to_luma_alpha8().into(),
+        ColorType::Rgb8 => image.to_rgb8().into(),
+        ColorType::Rgba8 => image.to_rgba8().into(),
+        ColorType::L16 => image.to_luma16().into(),
+        ColorType::La16 => image.to_luma_alpha16().into(),
+        ColorType::Rgb16 => image.to_rgb16().into(),
+        ColorType::Rgba16 => image.to_rgba16().into(),
+        ColorType::Rgb32F => image.to_rgb32f().into(),
+        _ => image.to_rgba32f().into(),
+    }
+}
+
+fn samples<P>(buffer: &ImageBuffer<P, Vec<P::Subpixel>>, x: u32, y: u32) -> Vec<f64>
+where
+    P: Pixel,
+    P::Subpixel: AsPrimitive<f64>,
+{
+    buffer
+        .get_pixel(x, y)
+        .channels()
+        .iter()
+        .map(|sample| sample.as_())
+        .collect()
+}
+
+fn pixel(image: &DynamicImage, x: u32, y: u32) -> Vec<f64> {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageLumaA8(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageRgb8(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageRgba8(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageLuma16(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageLumaA16(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageRgb16(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageRgba16(buffer) => samples(buffer, x, y),
+        DynamicImage::ImageRgb32F(buffer) => samples(buffer, x, y),
+        _ => samples(&image.to_rgba32f(), x, y),
+    }
+}
+
+/// Compares the decoded image to the original, pixel by pixel.
+///
+/// Both are compared in the richest of their colour types, so a codec may
+/// e.g. decode a grey image as RGB, or add an opaque alpha channel, without
+/// failing; dropping a channel or precision that was used does fail.
+pub fn verify(original: &DynamicImage, decoded: &DynamicImage) -> Result<(), LosslessMismatch> {
+    let expected = (original.width(), original.height());
+    let found = (decoded.width(), decoded.height());
+    if expected != found {
+        return Err(LosslessMismatch::Dimensions { expected, found });
+    }
+
+    let color = if covers(decoded.color(), original.color()) {
+        decoded.color()
+    } else {
+        original.color()
+    };
+    let original = to_color(original, color);
+    let decoded = to_color(decoded, color);
+
+    let bytes_per_pixel = color.bytes_per_pixel() as usize;
+    let mismatch = original
+        .as_bytes()
+        .chunks_exact(bytes_per_pixel)
+        .zip(decoded.as_bytes().chunks_exact(bytes_per_pixel))
+        .position(|(a, b)| a != b);
+    match mismatch {
+        None => Ok(()),
+        Some(index) => {
+            let x = (index % original.width() as usize) as u32;
+            let y = (index / original.width() as usize) as u32;
+            Err(LosslessMismatch::Pixel {
+                x,
+                y,
+                expected: pixel(&original, x, y),
+                found: pixel(&decoded, x, y),
+            })
+        }
+    }
+}
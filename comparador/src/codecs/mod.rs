@@ -1,194 +1,490 @@
 use core::{ops::Add, time::Duration};
-use std::{fs, io::BufReader, path::Path, process::Command, time::Instant};
+use std::{
+    collections::BTreeMap, fs, io::Cursor, path::Path, process::Command, sync::Arc,
+    time::Instant,
+};
 
-use image::{DynamicImage, ImageEncoder, ImageFormat};
+use image::{ColorType, DynamicImage, ImageEncoder, ImageError, ImageFormat};
 
-#[derive(Clone)]
-pub struct Codec {
-    pub name: String,
-    pub func: Box<fn(&DynamicImage, &Path) -> Option<Compression>>,
-}
+use crate::error::ComparadorError;
 
-impl Codec {
-    pub fn new(name: String, func: fn(&DynamicImage, &Path) -> Option<Compression>) -> Codec {
-        Codec {
-            name,
-            func: Box::new(func),
-        }
+pub mod lossless;
+pub mod timing;
+
+use lossless::LosslessMismatch;
+use timing::{Repetitions, Timing};
+
+/// An image codec, possibly carrying its own parameters (quality, speed, ...).
+///
+/// Implementations are constructed from data, so the same family can be
+/// instantiated many times with different parameters.
+pub trait Codec: Send + Sync {
+    /// Human readable name, unique among the codecs of an experiment.
+    fn name(&self) -> String;
+    /// Name of the codec family, shared by every parametrization.
+    fn family(&self) -> &'static str;
+    fn is_lossy(&self) -> bool;
+    /// Extension of the files holding encoded streams, e.g. `jpg`.
+    fn extension(&self) -> &'static str;
+    fn parameters(&self) -> BTreeMap<&'static str, String>;
+    /// Whether the codec can encode this image at all; unsupported images are skipped.
+    fn supports(&self, _image: &DynamicImage) -> bool {
+        true
     }
-    pub fn apply(&self, image: &DynamicImage, temp_file: &Path) -> Option<Compression> {
-        (self.func)(image, temp_file)
+    /// Encodes the image in memory. Only codecs relying on external processes
+    /// may write files, always derived from the unique `temp_file`.
+    fn encode(&self, image: &DynamicImage, temp_file: &Path) -> Result<Encoded, ComparadorError>;
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError>;
+
+    /// Encodes the image and decodes it back, timing both directions
+    /// over the given number of repetitions.
+    fn apply(
+        &self,
+        image: &DynamicImage,
+        temp_file: &Path,
+        repetitions: Repetitions,
+    ) -> Result<Compression, ComparadorError> {
+        let Repetitions { repeat, warmup } = repetitions;
+        let repeat = repeat.max(1);
+
+        let mut encode_times = Vec::with_capacity(repeat);
+        let mut stream = vec![];
+        for run in 0..warmup + repeat {
+            let encoded = self.encode(image, temp_file)?;
+            if run >= warmup {
+                encode_times.push(encoded.time_spent);
+            }
+            stream = encoded.stream;
+        }
+
+        let mut decode_times = Vec::with_capacity(repeat);
+        let mut decoded = None;
+        for run in 0..warmup + repeat {
+            let now = Instant::now();
+            let image = self.decode(&stream)?;
+            let decode_time = now.elapsed();
+            if run >= warmup {
+                decode_times.push(decode_time);
+            }
+            decoded = Some(image);
+        }
+        let decoded = decoded.expect("at least one decoding run");
+
+        let lossless_mismatch = if self.is_lossy() {
+            let expected = (image.width(), image.height());
+            let found = (decoded.width(), decoded.height());
+            if expected != found {
+                return Err(ComparadorError::DimensionMismatch { expected, found });
+            }
+            None
+        } else {
+            lossless::verify(image, &decoded).err()
+        };
+        Ok(Compression {
+            stream,
+            encode_time: Timing::from_samples(encode_times),
+            decode_time: Timing::from_samples(decode_times),
+            image_if_lossy: self.is_lossy().then_some(decoded),
+            lossless_mismatch,
+        })
     }
 }
 
-impl Add<(&DynamicImage, &Path)> for &Codec {
-    type Output = Option<Compression>;
+impl Add<(&DynamicImage, &Path)> for &dyn Codec {
+    type Output = Result<Compression, ComparadorError>;
 
     fn add(self, (img, temp_file): (&DynamicImage, &Path)) -> Self::Output {
-        (self.func)(img, temp_file)
+        self.apply(img, temp_file, Repetitions::default())
     }
 }
 
-pub struct Compression {
-    pub stream_size: u64,
+/// The codecs of the reference experiment: every family, with qualities
+/// Q = {90, 80, 50, 15} and palettes of 128 and 256 colours.
+pub fn defaults() -> Vec<Arc<dyn Codec>> {
+    const QUALITIES: [u8; 4] = [90, 80, 50, 15];
+    const PALETTE_SIZES: [u16; 2] = [128, 256];
+
+    // Lossless
+    let mut codecs: Vec<Arc<dyn Codec>> = vec![
+        Arc::new(PngCodec),
+        Arc::new(QoiCodec),
+        Arc::new(WebpCodec { quality: None }),
+    ];
+
+    // Lossy
+    codecs.extend(QUALITIES.map(|quality| {
+        Arc::new(WebpCodec {
+            quality: Some(quality as f32),
+        }) as Arc<dyn Codec>
+    }));
+    codecs.extend(QUALITIES.map(|quality| Arc::new(JpegCodec { quality }) as Arc<dyn Codec>));
+    codecs.extend(QUALITIES.map(|quality| Arc::new(AvifCodec::new(quality)) as Arc<dyn Codec>));
+    codecs.extend(
+        PALETTE_SIZES.map(|colors| Arc::new(PngQuantCodec { colors }) as Arc<dyn Codec>),
+    );
+
+    codecs
+}
+
+pub struct Encoded {
+    pub stream: Vec<u8>,
     pub time_spent: Duration,
+}
+
+pub struct Compression {
+    pub stream: Vec<u8>,
+    pub encode_time: Timing,
+    pub decode_time: Timing,
     pub image_if_lossy: Option<DynamicImage>,
+    /// For lossless codecs, where the round-trip first differed from the original.
+    pub lossless_mismatch: Option<LosslessMismatch>,
+}
+
+/// File name of a codec's encoded stream, e.g. `jpeg-15.jpg` for `JPEG (15%)`.
+pub fn artifact_name(codec: &dyn Codec) -> String {
+    let name = codec.name().to_ascii_lowercase();
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    format!("{}.{}", words.join("-"), codec.extension())
+}
+
+impl Compression {
+    /// Whether a lossless codec reproduced the original exactly; [`None`] for lossy codecs.
+    pub fn lossless_verified(&self) -> Option<bool> {
+        match self.image_if_lossy {
+            Some(_) => None,
+            None => Some(self.lossless_mismatch.is_none()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PngCodec;
+
+impl Codec for PngCodec {
+    fn name(&self) -> String {
+        String::from("PNG")
+    }
+    fn family(&self) -> &'static str {
+        "PNG"
+    }
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+    fn is_lossy(&self) -> bool {
+        false
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::new()
+    }
+
+    fn encode(&self, img: &DynamicImage, _: &Path) -> Result<Encoded, ComparadorError> {
+        let mut stream = Cursor::new(vec![]);
+        let now = Instant::now();
+        img.write_to(&mut stream, ImageFormat::Png)
+            .map_err(encode_error)?;
+        let time_spent = now.elapsed();
+
+        Ok(Encoded {
+            stream: stream.into_inner(),
+            time_spent,
+        })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        image::load_from_memory_with_format(stream, ImageFormat::Png).map_err(decode_error)
+    }
 }
 
-pub fn png(img: &DynamicImage, temp_file: &Path) -> Option<Compression> {
-    let temp_file = temp_file.with_extension("png");
-    let now = Instant::now();
-    img.save_with_format(&temp_file, ImageFormat::Png).ok()?;
-    let time_spent = now.elapsed();
-
-    let stream_size = fs::metadata(&temp_file).ok()?.len();
-    fs::remove_file(temp_file).ok()?;
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: None,
-    })
-}
-
-pub fn qoi(img: &DynamicImage, temp_file: &Path) -> Option<Compression> {
-    if !img.color().has_color() {
-        return None;
-    }
-    let temp_file = temp_file.with_extension("qoi");
-    let now = Instant::now();
-    img.save_with_format(&temp_file, ImageFormat::Qoi).ok()?;
-    let time_spent = now.elapsed();
-
-    let stream_size = fs::metadata(&temp_file).ok()?.len();
-    fs::remove_file(temp_file).ok()?;
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: None,
-    })
-}
-
-pub fn avif(img: &DynamicImage, temp_file: &Path, quality: u8) -> Option<Compression> {
-    let temp_file = temp_file.with_extension("avif");
-    use image::{
-        codecs::avif::{AvifDecoder, AvifEncoder},
-        ExtendedColorType,
-    };
-
-    let time_spent = {
-        let file = fs::File::options()
-            .write(true)
-            .create(true)
-            .open(&temp_file)
-            .ok()?;
-        let encoder = AvifEncoder::new_with_speed_quality(&file, 5, quality);
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QoiCodec;
+
+impl Codec for QoiCodec {
+    fn name(&self) -> String {
+        String::from("QOI")
+    }
+    fn family(&self) -> &'static str {
+        "QOI"
+    }
+    fn extension(&self) -> &'static str {
+        "qoi"
+    }
+    fn is_lossy(&self) -> bool {
+        false
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::new()
+    }
+
+    /// QOI only stores 8-bit RGB(A) images.
+    fn supports(&self, image: &DynamicImage) -> bool {
+        matches!(image.color(), ColorType::Rgb8 | ColorType::Rgba8)
+    }
 
+    fn encode(&self, img: &DynamicImage, _: &Path) -> Result<Encoded, ComparadorError> {
+        let mut stream = Cursor::new(vec![]);
         let now = Instant::now();
-        encoder
-            .write_image(
-                img.to_rgb8().as_raw(),
-                img.width(),
-                img.height(),
-                ExtendedColorType::Rgb8,
-            )
-            .ok()?;
-        now.elapsed()
-    };
-
-    let (stream_size, img) = {
-        let file = fs::File::open(&temp_file).ok()?;
-        let stream_size = file.metadata().ok()?.len();
-        let decoder = AvifDecoder::new(file).ok()?;
-        let img = DynamicImage::from_decoder(decoder).ok()?;
-        (stream_size, img)
-    };
-    fs::remove_file(temp_file).ok()?;
-
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: Some(img),
-    })
-}
-
-pub fn webp(img: &DynamicImage, quality: Option<f32>) -> Option<Compression> {
-    use webp::{Decoder, Encoder};
-
-    let now = Instant::now();
-    let encoder = Encoder::from_image(img).ok()?;
-    let encoded = if let Some(q) = quality {
-        encoder.encode(q)
-    } else {
-        encoder.encode_lossless()
-    };
-    let time_spent = now.elapsed();
-
-    let stream_size = encoded.len() as u64;
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: quality.map(|_| Decoder::new(&encoded).decode().unwrap().to_image()),
-    })
-}
-
-pub fn jpeg(img: &DynamicImage, temp_file: &Path, quality: u8) -> Option<Compression> {
-    let temp_file = temp_file.with_extension("jpg");
-    use image::codecs::jpeg::{JpegDecoder, JpegEncoder};
-    let time_spent = {
-        fs::remove_file(&temp_file).unwrap_or_default();
-        let file = fs::File::create_new(&temp_file).ok()?;
-        let mut encoder = JpegEncoder::new_with_quality(&file, quality);
+        img.write_to(&mut stream, ImageFormat::Qoi)
+            .map_err(encode_error)?;
+        let time_spent = now.elapsed();
+
+        Ok(Encoded {
+            stream: stream.into_inner(),
+            time_spent,
+        })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        image::load_from_memory_with_format(stream, ImageFormat::Qoi).map_err(decode_error)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AvifCodec {
+    pub quality: u8,
+    pub speed: u8,
+}
+
+impl AvifCodec {
+    pub const DEFAULT_SPEED: u8 = 5;
+
+    pub fn new(quality: u8) -> AvifCodec {
+        AvifCodec {
+            quality,
+            speed: Self::DEFAULT_SPEED,
+        }
+    }
+}
+
+impl Codec for AvifCodec {
+    fn name(&self) -> String {
+        if self.speed == Self::DEFAULT_SPEED {
+            format!("AVIF ({}%)", self.quality)
+        } else {
+            format!("AVIF ({}%, speed {})", self.quality, self.speed)
+        }
+    }
+    fn family(&self) -> &'static str {
+        "AVIF"
+    }
+    fn extension(&self) -> &'static str {
+        "avif"
+    }
+    fn is_lossy(&self) -> bool {
+        true
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([
+            ("quality", self.quality.to_string()),
+            ("speed", self.speed.to_string()),
+        ])
+    }
+
+    fn encode(&self, img: &DynamicImage, _: &Path) -> Result<Encoded, ComparadorError> {
+        use image::{codecs::avif::AvifEncoder, ExtendedColorType};
+
+        let mut stream = vec![];
+        let time_spent = {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut stream, self.speed, self.quality);
+
+            let now = Instant::now();
+            encoder
+                .write_image(
+                    img.to_rgb8().as_raw(),
+                    img.width(),
+                    img.height(),
+                    ExtendedColorType::Rgb8,
+                )
+                .map_err(encode_error)?;
+            now.elapsed()
+        };
+
+        Ok(Encoded { stream, time_spent })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        use image::codecs::avif::AvifDecoder;
+
+        let decoder = AvifDecoder::new(Cursor::new(stream)).map_err(decode_error)?;
+        DynamicImage::from_decoder(decoder).map_err(decode_error)
+    }
+}
+
+/// WebP, lossless when `quality` is [`None`].
+#[derive(Clone, Copy, Debug)]
+pub struct WebpCodec {
+    pub quality: Option<f32>,
+}
+
+impl Codec for WebpCodec {
+    fn name(&self) -> String {
+        match self.quality {
+            Some(quality) => format!("WEBP ({quality}%)"),
+            None => String::from("WEBP"),
+        }
+    }
+    fn family(&self) -> &'static str {
+        "WEBP"
+    }
+    fn extension(&self) -> &'static str {
+        "webp"
+    }
+    fn is_lossy(&self) -> bool {
+        self.quality.is_some()
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        self.quality
+            .map(|quality| ("quality", quality.to_string()))
+            .into_iter()
+            .collect()
+    }
+
+    /// libwebp only encodes 8-bit RGB(A) images.
+    fn supports(&self, image: &DynamicImage) -> bool {
+        matches!(image.color(), ColorType::Rgb8 | ColorType::Rgba8)
+    }
+
+    fn encode(&self, img: &DynamicImage, _: &Path) -> Result<Encoded, ComparadorError> {
+        use webp::Encoder;
 
         let now = Instant::now();
-        encoder.encode_image(img).ok()?;
-        now.elapsed()
-    };
-
-    let file = fs::File::open(&temp_file).ok()?;
-    let stream_size = file.metadata().ok()?.len();
-
-    let buf = BufReader::new(file);
-    let decoder = JpegDecoder::new(buf).ok()?;
-    let img = DynamicImage::from_decoder(decoder).ok()?;
-
-    fs::remove_file(temp_file).ok()?;
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: Some(img),
-    })
-}
-
-pub fn png_quant(img: &DynamicImage, temp_file: &Path, ncolors: u16) -> Option<Compression> {
-    let temp_file = temp_file.with_extension("png");
-    let temp_file_2 = temp_file.with_extension("new.png");
-
-    let time_spent = Instant::now();
-    img.save_with_format(&temp_file, ImageFormat::Png).ok()?;
-    {
-        let mut cmd = Command::new("pngquant");
-        cmd.args([
-            "--speed",
-            "1",
-            "--ext",
-            ".new.png",
-            ncolors.to_string().as_str(),
-            temp_file.to_str()?,
-        ]);
-        let mut child = cmd.spawn().expect("Expected pngquant to be installed");
-        child.wait().ok()?;
-    }
-    let time_spent = time_spent.elapsed();
-
-    // dbg!(&temp_file);
-    // dbg!(&temp_file_2);
-    let stream_size = fs::metadata(&temp_file_2).ok()?.len();
-    let img = image::open(&temp_file_2).ok()?;
-    fs::remove_file(temp_file).ok()?;
-    fs::remove_file(temp_file_2).ok()?;
-    Some(Compression {
-        stream_size,
-        time_spent,
-        image_if_lossy: Some(img),
-    })
+        let encoder =
+            Encoder::from_image(img).map_err(|error| ComparadorError::Encode(error.to_owned()))?;
+        let encoded = if let Some(q) = self.quality {
+            encoder.encode(q)
+        } else {
+            encoder.encode_lossless()
+        };
+        let time_spent = now.elapsed();
+
+        Ok(Encoded {
+            stream: encoded.to_vec(),
+            time_spent,
+        })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        use webp::Decoder;
+
+        let decoded = Decoder::new(stream)
+            .decode()
+            .ok_or_else(|| ComparadorError::Decode(String::from("invalid WebP stream")))?;
+        Ok(decoded.to_image())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct JpegCodec {
+    pub quality: u8,
+}
+
+impl Codec for JpegCodec {
+    fn name(&self) -> String {
+        format!("JPEG ({}%)", self.quality)
+    }
+    fn family(&self) -> &'static str {
+        "JPEG"
+    }
+    fn extension(&self) -> &'static str {
+        "jpg"
+    }
+    fn is_lossy(&self) -> bool {
+        true
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([("quality", self.quality.to_string())])
+    }
+
+    fn encode(&self, img: &DynamicImage, _: &Path) -> Result<Encoded, ComparadorError> {
+        use image::codecs::jpeg::JpegEncoder;
+
+        let mut stream = vec![];
+        let time_spent = {
+            let mut encoder = JpegEncoder::new_with_quality(&mut stream, self.quality);
+
+            let now = Instant::now();
+            encoder.encode_image(img).map_err(encode_error)?;
+            now.elapsed()
+        };
+
+        Ok(Encoded { stream, time_spent })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        use image::codecs::jpeg::JpegDecoder;
+
+        let decoder = JpegDecoder::new(Cursor::new(stream)).map_err(decode_error)?;
+        DynamicImage::from_decoder(decoder).map_err(decode_error)
+    }
+}
+
+/// PNG palette quantization through the external `pngquant` tool.
+#[derive(Clone, Copy, Debug)]
+pub struct PngQuantCodec {
+    pub colors: u16,
+}
+
+impl Codec for PngQuantCodec {
+    fn name(&self) -> String {
+        format!("PNG (pngquant {})", self.colors)
+    }
+    fn family(&self) -> &'static str {
+        "PNGQUANT"
+    }
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+    fn is_lossy(&self) -> bool {
+        true
+    }
+    fn parameters(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([("colors", self.colors.to_string())])
+    }
+
+    fn encode(&self, img: &DynamicImage, temp_file: &Path) -> Result<Encoded, ComparadorError> {
+        let temp_file = temp_file.with_extension("png");
+        let temp_file_2 = temp_file.with_extension("new.png");
+
+        let time_spent = Instant::now();
+        img.save_with_format(&temp_file, ImageFormat::Png)
+            .map_err(encode_error)?;
+        let status = Command::new("pngquant")
+            .args(["--speed", "1", "--ext", ".new.png"])
+            .arg(self.colors.to_string())
+            .arg(&temp_file)
+            .status();
+        let time_spent = time_spent.elapsed();
+
+        let stream = match status {
+            Ok(status) if status.success() => fs::read(&temp_file_2).map_err(ComparadorError::Io),
+            Ok(status) => Err(ComparadorError::ExternalTool {
+                tool: "pngquant",
+                reason: status.to_string(),
+            }),
+            Err(error) => Err(ComparadorError::ExternalTool {
+                tool: "pngquant",
+                reason: format!("could not run it ({error}), is it installed?"),
+            }),
+        };
+        fs::remove_file(temp_file).unwrap_or_default();
+        fs::remove_file(temp_file_2).unwrap_or_default();
+        Ok(Encoded {
+            stream: stream?,
+            time_spent,
+        })
+    }
+
+    fn decode(&self, stream: &[u8]) -> Result<DynamicImage, ComparadorError> {
+        image::load_from_memory_with_format(stream, ImageFormat::Png).map_err(decode_error)
+    }
+}
+
+fn encode_error(error: ImageError) -> ComparadorError {
+    ComparadorError::Encode(error.to_string())
+}
+
+fn decode_error(error: ImageError) -> ComparadorError {
+    ComparadorError::Decode(error.to_string())
 }
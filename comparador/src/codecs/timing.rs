@@ -0,0 +1,56 @@
+//! Repeated timing runs and their robust statistics.
+use core::time::Duration;
+
+/// How many times each direction is run: `warmup` untimed runs, then `repeat` timed ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Repetitions {
+    pub repeat: usize,
+    pub warmup: usize,
+}
+
+impl Default for Repetitions {
+    fn default() -> Self {
+        Repetitions {
+            repeat: 1,
+            warmup: 0,
+        }
+    }
+}
+
+/// Minimum, median and median absolute deviation (MAD) of timing samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub min: Duration,
+    pub median: Duration,
+    pub mad: Duration,
+}
+
+fn median(sorted: &[Duration]) -> Duration {
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2
+    } else {
+        sorted[middle]
+    }
+}
+
+impl Timing {
+    /// Statistics of the given samples; all zeroes when there are none.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Timing {
+        if samples.is_empty() {
+            return Timing::default();
+        }
+        samples.sort_unstable();
+        let median = median(&samples);
+        let mut deviations: Vec<Duration> = samples
+            .iter()
+            .map(|&sample| sample.abs_diff(median))
+            .collect();
+        deviations.sort_unstable();
+        Timing {
+            min: samples[0],
+            median,
+            mad: self::median(&deviations),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! Declarative experiment configuration.
+//!
+//! ```toml
+//! metrics = ["MAE", "SSIM", "GMSD"]
+//! hashes = ["P Hash"]
+//!
+//! [[codecs]]
+//! family = "JPEG"
+//! quality = [90, 80, 50, 15]
+//!
+//! [[codecs]]
+//! family = "AVIF"
+//! quality = [50]
+//! speed = [3, 5, 8]
+//!
+//! [[codecs]]
+//! family = "WEBP"
+//! quality = [90]
+//! lossless = true
+//!
+//! [[codecs]]
+//! family = "PNGQUANT"
+//! colors = [64, 128, 256]
+//! ```
+//!
+//! Omitting `codecs`, `metrics` or `hashes` keeps the defaults of the reference
+//! experiment, which leave out the high-cost metrics; an empty list disables
+//! that section.
+use crate::{
+    codecs::{self, AvifCodec, Codec, JpegCodec, PngCodec, PngQuantCodec, QoiCodec, WebpCodec},
+    metrics::{
+        self,
+        hash::{self, HashMetric},
+        Metric,
+    },
+};
+
+use core::fmt;
+use std::{collections::HashSet, fs, io, path::Path, sync::Arc};
+
+use globset::GlobBuilder;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+    pub codecs: Option<Vec<CodecConfig>>,
+    pub metrics: Option<Vec<String>>,
+    pub hashes: Option<Vec<String>>,
+}
+
+/// A codec family and its parameter grid. Every combination of the listed
+/// parameters becomes one codec.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CodecConfig {
+    pub family: String,
+    #[serde(default)]
+    pub quality: Vec<u8>,
+    #[serde(default)]
+    pub speed: Vec<u8>,
+    #[serde(default)]
+    pub colors: Vec<u16>,
+    /// Only meaningful for WebP, which has a lossless mode besides its qualities.
+    #[serde(default)]
+    pub lossless: bool,
+}
+
+/// The codecs, metrics and hashes an experiment runs.
+#[derive(Clone)]
+pub struct Experiment {
+    pub codecs: Vec<Arc<dyn Codec>>,
+    pub metrics: Vec<Metric<f64>>,
+    pub hashes: Vec<HashMetric<u64>>,
+}
+
+impl Default for Experiment {
+    fn default() -> Self {
+        Experiment {
+            codecs: codecs::defaults(),
+            metrics: metrics::defaults(),
+            hashes: hash::all(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    UnknownCodecFamily(String),
+    UnknownMetric(String),
+    UnknownHash(String),
+    InvalidParameter {
+        family: String,
+        parameter: &'static str,
+        reason: String,
+    },
+    DuplicateCodec(String),
+    InvalidPattern(globset::Error),
+    /// A filter pattern that matches no codec, metric or hash.
+    NoMatch {
+        kind: &'static str,
+        pattern: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read the configuration: {error}"),
+            ConfigError::Parse(error) => write!(f, "invalid configuration: {error}"),
+            ConfigError::UnknownCodecFamily(name) => write!(
+                f,
+                "unknown codec family {name:?} (expected one of {})",
+                FAMILIES.join(", ")
+            ),
+            ConfigError::UnknownMetric(name) => write!(
+                f,
+                "unknown metric {name:?} (expected one of {})",
+                names(metrics::all().iter().map(|metric| &metric.name))
+            ),
+            ConfigError::UnknownHash(name) => write!(
+                f,
+                "unknown hash {name:?} (expected one of {})",
+                names(hash::all().iter().map(|hash| &hash.name))
+            ),
+            ConfigError::InvalidParameter {
+                family,
+                parameter,
+                reason,
+            } => write!(f, "invalid `{parameter}` for codec family {family}: {reason}"),
+            ConfigError::DuplicateCodec(name) => write!(f, "codec {name:?} is declared twice"),
+            ConfigError::InvalidPattern(error) => write!(f, "invalid pattern: {error}"),
+            ConfigError::NoMatch { kind, pattern } => {
+                write!(f, "no {kind} matches {pattern:?} (see the `list` subcommand)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+const FAMILIES: [&str; 6] = ["PNG", "QOI", "WEBP", "JPEG", "AVIF", "PNGQUANT"];
+
+fn names<'a>(names: impl Iterator<Item = &'a String>) -> String {
+    names.map(String::as_str).collect::<Vec<_>>().join(", ")
+}
+
+impl Experiment {
+    /// Makes every metric available to [`Experiment::filter`], the high-cost
+    /// ones left out of the defaults included.
+    pub fn with_every_metric(self) -> Experiment {
+        Experiment {
+            metrics: metrics::all(),
+            ..self
+        }
+    }
+
+    /// Keeps only the codecs, metrics and hashes whose names match one of the
+    /// corresponding patterns; an empty pattern list keeps every entry.
+    ///
+    /// Patterns are case-insensitive globs, e.g. `JPEG*` or `"PNG (pngquant ?28)"`,
+    /// and each must match at least one entry.
+    pub fn filter(
+        self,
+        codecs: &[String],
+        metrics: &[String],
+        hashes: &[String],
+    ) -> Result<Experiment, ConfigError> {
+        Ok(Experiment {
+            codecs: filter(self.codecs, codecs, "codec", |codec| codec.name())?,
+            metrics: filter(self.metrics, metrics, "metric", |metric| metric.name.clone())?,
+            hashes: filter(self.hashes, hashes, "hash", |hash| hash.name.clone())?,
+        })
+    }
+}
+
+fn filter<T>(
+    entries: Vec<T>,
+    patterns: &[String],
+    kind: &'static str,
+    name_of: impl Fn(&T) -> String,
+) -> Result<Vec<T>, ConfigError> {
+    if patterns.is_empty() {
+        return Ok(entries);
+    }
+    let matchers = patterns
+        .iter()
+        .map(|pattern| {
+            GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(false)
+                .build()
+                .map(|glob| glob.compile_matcher())
+                .map_err(ConfigError::InvalidPattern)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names: Vec<String> = entries.iter().map(&name_of).collect();
+    for (pattern, matcher) in patterns.iter().zip(&matchers) {
+        if !names.iter().any(|name| matcher.is_match(name)) {
+            return Err(ConfigError::NoMatch {
+                kind,
+                pattern: pattern.clone(),
+            });
+        }
+    }
+    Ok(entries
+        .into_iter()
+        .zip(names)
+        .filter(|(_, name)| matchers.iter().any(|matcher| matcher.is_match(name)))
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+impl ExperimentConfig {
+    pub fn from_file(path: &Path) -> Result<ExperimentConfig, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&text).map_err(ConfigError::Parse)
+    }
+
+    /// Validates every name and parameter, building the experiment.
+    pub fn build(self) -> Result<Experiment, ConfigError> {
+        let defaults = Experiment::default();
+
+        let codecs = match self.codecs {
+            Some(configs) => {
+                let mut codecs = vec![];
+                for config in configs {
+                    codecs.extend(config.build()?);
+                }
+                codecs
+            }
+            None => defaults.codecs,
+        };
+        let mut seen = HashSet::new();
+        for codec in &codecs {
+            if !seen.insert(codec.name()) {
+                return Err(ConfigError::DuplicateCodec(codec.name()));
+            }
+        }
+
+        let metrics = match self.metrics {
+            Some(names) => select(names, &metrics::all(), |metric| &metric.name)
+                .map_err(ConfigError::UnknownMetric)?,
+            None => defaults.metrics,
+        };
+        let hashes = match self.hashes {
+            Some(names) => select(names, &defaults.hashes, |hash| &hash.name)
+                .map_err(ConfigError::UnknownHash)?,
+            None => defaults.hashes,
+        };
+
+        Ok(Experiment {
+            codecs,
+            metrics,
+            hashes,
+        })
+    }
+}
+
+/// Picks the entries named in `names`, in that order, failing on the first unknown name.
+fn select<T: Clone>(
+    names: Vec<String>,
+    available: &[T],
+    name_of: impl Fn(&T) -> &String,
+) -> Result<Vec<T>, String> {
+    names
+        .into_iter()
+        .map(|name| {
+            available
+                .iter()
+                .find(|entry| name_of(entry).eq_ignore_ascii_case(&name))
+                .cloned()
+                .ok_or(name)
+        })
+        .collect()
+}
+
+impl CodecConfig {
+    fn build(self) -> Result<Vec<Arc<dyn Codec>>, ConfigError> {
+        let family = self.family.to_ascii_uppercase();
+        let invalid = |parameter: &'static str, reason: &str| ConfigError::InvalidParameter {
+            family: family.clone(),
+            parameter,
+            reason: reason.to_owned(),
+        };
+        let unused = |parameter: &'static str, present: bool| {
+            if present {
+                Err(invalid(parameter, "not supported by this family"))
+            } else {
+                Ok(())
+            }
+        };
+        let required = |parameter: &'static str, present: bool| {
+            if present {
+                Ok(())
+            } else {
+                Err(invalid(parameter, "at least one value is required"))
+            }
+        };
+        if let Some(quality) = self.quality.iter().find(|&&quality| quality > 100) {
+            return Err(invalid(
+                "quality",
+                &format!("{quality} is outside of 0..=100"),
+            ));
+        }
+
+        let codecs: Vec<Arc<dyn Codec>> = match family.as_str() {
+            "PNG" | "QOI" => {
+                unused("quality", !self.quality.is_empty())?;
+                unused("speed", !self.speed.is_empty())?;
+                unused("colors", !self.colors.is_empty())?;
+                unused("lossless", self.lossless)?;
+                if family == "PNG" {
+                    vec![Arc::new(PngCodec)]
+                } else {
+                    vec![Arc::new(QoiCodec)]
+                }
+            }
+            "WEBP" => {
+                unused("speed", !self.speed.is_empty())?;
+                unused("colors", !self.colors.is_empty())?;
+                if self.quality.is_empty() && !self.lossless {
+                    return Err(invalid(
+                        "quality",
+                        "at least one value is required, unless `lossless = true`",
+                    ));
+                }
+                let lossless = self.lossless.then_some(None);
+                lossless
+                    .into_iter()
+                    .chain(self.quality.iter().map(|&quality| Some(quality as f32)))
+                    .map(|quality| Arc::new(WebpCodec { quality }) as Arc<dyn Codec>)
+                    .collect()
+            }
+            "JPEG" => {
+                required("quality", !self.quality.is_empty())?;
+                unused("speed", !self.speed.is_empty())?;
+                unused("colors", !self.colors.is_empty())?;
+                unused("lossless", self.lossless)?;
+                self.quality
+                    .iter()
+                    .map(|&quality| Arc::new(JpegCodec { quality }) as Arc<dyn Codec>)
+                    .collect()
+            }
+            "AVIF" => {
+                required("quality", !self.quality.is_empty())?;
+                unused("colors", !self.colors.is_empty())?;
+                unused("lossless", self.lossless)?;
+                if let Some(speed) = self.speed.iter().find(|speed| !(1..=10).contains(*speed)) {
+                    return Err(invalid("speed", &format!("{speed} is outside of 1..=10")));
+                }
+                let speeds = if self.speed.is_empty() {
+                    vec![AvifCodec::DEFAULT_SPEED]
+                } else {
+                    self.speed
+                };
+                self.quality
+                    .iter()
+                    .flat_map(|&quality| {
+                        speeds.iter().map(move |&speed| {
+                            Arc::new(AvifCodec { quality, speed }) as Arc<dyn Codec>
+                        })
+                    })
+                    .collect()
+            }
+            "PNGQUANT" => {
+                required("colors", !self.colors.is_empty())?;
+                unused("quality", !self.quality.is_empty())?;
+                unused("speed", !self.speed.is_empty())?;
+                unused("lossless", self.lossless)?;
+                if let Some(colors) = self.colors.iter().find(|colors| !(2..=256).contains(*colors))
+                {
+                    return Err(invalid(
+                        "colors",
+                        &format!("{colors} is outside of 2..=256"),
+                    ));
+                }
+                self.colors
+                    .iter()
+                    .map(|&colors| Arc::new(PngQuantCodec { colors }) as Arc<dyn Codec>)
+                    .collect()
+            }
+            _ => return Err(ConfigError::UnknownCodecFamily(self.family)),
+        };
+        Ok(codecs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::metrics::Cost;
+
+    #[test]
+    fn defaults_leave_out_high_cost_metrics() {
+        let experiment = Experiment::default();
+        assert!(experiment.metrics.iter().all(|metric| metric.cost < Cost::High));
+        assert!(!experiment.metrics.iter().any(|metric| metric.name == "FSIM"));
+    }
+
+    #[test]
+    fn high_cost_metrics_run_when_named() {
+        let experiment = Experiment::default()
+            .with_every_metric()
+            .filter(&[], &[String::from("FSIM"), String::from("VIFp")], &[])
+            .unwrap();
+        let names: Vec<&str> = experiment.metrics.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["VIFp", "FSIM"]);
+
+        let config: ExperimentConfig = toml::from_str(r#"metrics = ["SSIMULACRA2"]"#).unwrap();
+        assert_eq!(config.build().unwrap().metrics[0].name, "SSIMULACRA2");
+    }
+}
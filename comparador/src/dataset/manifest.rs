@@ -0,0 +1,188 @@
+//! Manifests listing the exact files of a dataset, so runs on different
+//! machines can prove they processed identical data.
+use crate::error::{ComparadorError, Failure};
+
+use core::fmt;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Cursor},
+    path::{Path, PathBuf},
+};
+
+use image::ImageReader;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// One file of the dataset, as a row of the CSV manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the dataset folder.
+    pub image: PathBuf,
+    /// Size of the file, in bytes.
+    pub size: u64,
+    pub sha256: String,
+    pub width: u32,
+    pub height: u32,
+    /// Colour type of the decoded image, e.g. `Rgb8`.
+    pub color: String,
+    /// Format the file was decoded as, e.g. `Png`.
+    pub format: String,
+}
+
+impl ManifestEntry {
+    /// Reads, fingerprints and decodes one image of the dataset.
+    pub fn new(root: &Path, image: &Path) -> Result<ManifestEntry, ComparadorError> {
+        let bytes = fs::read(image)?;
+        let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
+        let format = reader
+            .format()
+            .ok_or_else(|| ComparadorError::Decode(String::from("unknown image format")))?;
+        let decoded = reader
+            .decode()
+            .map_err(|error| ComparadorError::Decode(error.to_string()))?;
+        Ok(ManifestEntry {
+            image: image.strip_prefix(root).unwrap_or(image).to_path_buf(),
+            size: bytes.len() as u64,
+            sha256: hex_digest(&bytes),
+            width: decoded.width(),
+            height: decoded.height(),
+            color: format!("{:?}", decoded.color()),
+            format: format!("{format:?}"),
+        })
+    }
+}
+
+/// Lowercase hexadecimal SHA-256 of `data`.
+fn hex_digest(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    to_hex(&hasher.finalize())
+}
+
+/// Lowercase hexadecimal SHA-256 of a file, read in chunks.
+fn file_digest(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Indexes every image, in parallel, keeping their order. Images that can not
+/// be read or decoded are returned apart.
+pub fn index(root: &Path, images: &[PathBuf]) -> (Vec<ManifestEntry>, Vec<Failure>) {
+    let results: Vec<_> = images
+        .par_iter()
+        .map(|image| ManifestEntry::new(root, image).map_err(|error| (image, error)))
+        .collect();
+
+    let mut entries = vec![];
+    let mut failures = vec![];
+    for result in results {
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err((image, error)) => failures.push(Failure {
+                image: image.clone(),
+                codec: None,
+                error,
+            }),
+        }
+    }
+    (entries, failures)
+}
+
+pub fn write_manifest(path: &Path, entries: &[ManifestEntry]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(BufWriter::new(File::create(path)?));
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()
+}
+
+pub fn read_manifest(path: &Path) -> io::Result<Vec<ManifestEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    csv::Reader::from_reader(reader)
+        .deserialize()
+        .map(|entry| entry.map_err(io::Error::from))
+        .collect()
+}
+
+/// A dataset file that differs from its manifest entry.
+#[derive(Debug)]
+pub enum Mismatch {
+    Unreadable {
+        image: PathBuf,
+        error: io::Error,
+    },
+    Size {
+        image: PathBuf,
+        expected: u64,
+        found: u64,
+    },
+    Digest {
+        image: PathBuf,
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Unreadable { image, error } => {
+                write!(f, "{}: could not be read, {error}", image.display())
+            }
+            Mismatch::Size {
+                image,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {expected} bytes, found {found}",
+                image.display()
+            ),
+            Mismatch::Digest {
+                image,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected SHA-256 {expected}, found {found}",
+                image.display()
+            ),
+        }
+    }
+}
+
+/// Checks the size and SHA-256 of every file listed in the manifest, in parallel.
+pub fn verify(root: &Path, entries: &[ManifestEntry]) -> Vec<Mismatch> {
+    entries
+        .par_iter()
+        .filter_map(|entry| {
+            let image = root.join(&entry.image);
+            let found = match fs::metadata(&image) {
+                Ok(metadata) => metadata.len(),
+                Err(error) => return Some(Mismatch::Unreadable { image, error }),
+            };
+            if found != entry.size {
+                return Some(Mismatch::Size {
+                    image,
+                    expected: entry.size,
+                    found,
+                });
+            }
+            match file_digest(&image) {
+                Ok(found) if found == entry.sha256 => None,
+                Ok(found) => Some(Mismatch::Digest {
+                    image,
+                    expected: entry.sha256.clone(),
+                    found,
+                }),
+                Err(error) => Some(Mismatch::Unreadable { image, error }),
+            }
+        })
+        .collect()
+}
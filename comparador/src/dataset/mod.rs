@@ -0,0 +1,404 @@
+//! Dataset discovery, sampling and partition into analysis and validation sets.
+//!
+//! Splits, and optionally samples, are stratified by folder, so every class of
+//! a dataset such as the Brain Tumor MRI one keeps its proportions in each set.
+pub mod manifest;
+
+use core::{fmt, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use globwalk::glob;
+use sha2::{Digest, Sha256};
+
+/// Extensions of the image files looked for in a dataset.
+pub const EXTENSIONS: &str = "avif,bmp,exr,gif,jpeg,jpg,ico,png,pnm,tga,tiff,qoi,webp";
+
+/// Every image file under `root`, sorted so runs see them in a stable order.
+pub fn find_images(root: &Path) -> Result<Vec<PathBuf>, globwalk::GlobError> {
+    let pattern = format!("{}/**/*.{{{EXTENSIONS}}}", root.display());
+    let mut images: Vec<PathBuf> = glob(pattern)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file())
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+/// SplitMix64, a small seeded generator: runs must be reproducible from their seed alone.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Named fractions of the dataset, e.g. `analysis=0.8,validation=0.2`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplitSpec(pub Vec<(String, f64)>);
+
+impl FromStr for SplitSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<(String, f64)> = vec![];
+        for part in text.split(',') {
+            let (name, fraction) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name=fraction`, found {part:?}"))?;
+            let name = name.trim();
+            let fraction: f64 = fraction
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid fraction {fraction:?} for {name:?}"))?;
+            if name.is_empty() {
+                return Err(format!("missing name in {part:?}"));
+            }
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(format!("fraction of {name:?} is outside of [0, 1]"));
+            }
+            if parts.iter().any(|(known, _)| known == name) {
+                return Err(format!("{name:?} is listed twice"));
+            }
+            parts.push((name.to_owned(), fraction));
+        }
+        let total: f64 = parts.iter().map(|(_, fraction)| fraction).sum();
+        if (total - 1.0).abs() > 1e-6 {
+            return Err(format!("fractions add up to {total}, instead of 1"));
+        }
+        Ok(SplitSpec(parts))
+    }
+}
+
+impl fmt::Display for SplitSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|(name, fraction)| format!("{name}={fraction}"))
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// How many images a run keeps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sample {
+    Count(usize),
+    /// In `[0, 1]`.
+    Fraction(f64),
+}
+
+impl Sample {
+    fn size(self, total: usize) -> usize {
+        match self {
+            Sample::Count(count) => count.min(total),
+            Sample::Fraction(fraction) => (fraction * total as f64).round() as usize,
+        }
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sample::Count(count) => write!(f, "{count}"),
+            Sample::Fraction(fraction) => write!(f, "{}%", fraction * 100.0),
+        }
+    }
+}
+
+/// Parses what [`Sample`]'s `Display` writes: a count, or a percentage.
+impl FromStr for Sample {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.strip_suffix('%') {
+            Some(percentage) => percentage
+                .trim()
+                .parse::<f64>()
+                .map(|percentage| Sample::Fraction(percentage / 100.0))
+                .map_err(|_| format!("invalid percentage {text:?}")),
+            None => text
+                .trim()
+                .parse()
+                .map(Sample::Count)
+                .map_err(|_| format!("invalid sample size {text:?}")),
+        }
+    }
+}
+
+/// Draws a reproducible subset of the images, in their original order.
+///
+/// When `stratified`, each folder contributes in proportion to its size.
+pub fn sample(
+    root: &Path,
+    images: &[PathBuf],
+    sample: Sample,
+    seed: u64,
+    stratified: bool,
+) -> Vec<PathBuf> {
+    let size = sample.size(images.len());
+    let mut strata: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for image in images {
+        let key = if stratified {
+            stratum(root, image)
+        } else {
+            PathBuf::new()
+        };
+        strata.entry(key).or_default().push(image);
+    }
+    let weights: Vec<f64> = strata.values().map(|members| members.len() as f64).collect();
+
+    let mut rng = Rng::new(seed);
+    let mut chosen = HashSet::new();
+    for (mut members, size) in strata.into_values().zip(apportion(&weights, size)) {
+        members.sort();
+        rng.shuffle(&mut members);
+        chosen.extend(members.into_iter().take(size));
+    }
+    images
+        .iter()
+        .filter(|image| chosen.contains(image))
+        .cloned()
+        .collect()
+}
+
+/// One of `count` disjoint parts of a dataset, numbered from 1, e.g. `2/4`.
+///
+/// Images are assigned by a hash of their path relative to the dataset folder,
+/// so every machine agrees on the partition without listing the same files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    pub fn contains(&self, root: &Path, image: &Path) -> bool {
+        let relative = image.strip_prefix(root).unwrap_or(image);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let mut hasher = Sha256::new();
+        hasher.update(relative.as_bytes());
+        let digest = hasher.finalize();
+        let hash = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        hash % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (index, count) = text
+            .split_once('/')
+            .ok_or_else(|| format!("expected `index/count`, found {text:?}"))?;
+        let index: usize = index
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard index {index:?}"))?;
+        let count: usize = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard count {count:?}"))?;
+        if !(1..=count).contains(&index) {
+            return Err(format!("shard index must be between 1 and {count}"));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Folder of `image` relative to `root`, the stratum it is split within.
+pub fn stratum(root: &Path, image: &Path) -> PathBuf {
+    let parent = image.parent().unwrap_or(Path::new(""));
+    parent.strip_prefix(root).unwrap_or(parent).to_path_buf()
+}
+
+/// Shares `count` items proportionally to `weights`, rounding by largest remainder so they add up.
+fn apportion(weights: &[f64], count: usize) -> Vec<usize> {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return vec![0; weights.len()];
+    }
+    let exact: Vec<f64> = weights
+        .iter()
+        .map(|weight| weight / total * count as f64)
+        .collect();
+    let mut sizes: Vec<usize> = exact.iter().map(|size| size.floor() as usize).collect();
+    let mut by_remainder: Vec<usize> = (0..sizes.len()).collect();
+    by_remainder
+        .sort_by(|&a, &b| (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor())));
+    let missing = count.saturating_sub(sizes.iter().sum());
+    for &part in by_remainder.iter().take(missing) {
+        sizes[part] += 1;
+    }
+    sizes
+}
+
+/// Assigns every image to a part of `spec`, shuffling each folder with `seed`.
+pub fn split(
+    root: &Path,
+    images: &[PathBuf],
+    spec: &SplitSpec,
+    seed: u64,
+) -> BTreeMap<PathBuf, String> {
+    let mut strata: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for image in images {
+        strata.entry(stratum(root, image)).or_default().push(image);
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut assignment = BTreeMap::new();
+    for mut members in strata.into_values() {
+        members.sort();
+        rng.shuffle(&mut members);
+        let mut members = members.into_iter();
+        let fractions: Vec<f64> = spec.0.iter().map(|(_, fraction)| *fraction).collect();
+        for ((name, _), size) in spec.0.iter().zip(apportion(&fractions, members.len())) {
+            for image in members.by_ref().take(size) {
+                assignment.insert(image.clone(), name.clone());
+            }
+        }
+    }
+    assignment
+}
+
+/// Writes the split as a CSV manifest of `image,stratum,split` rows, with paths relative to `root`.
+pub fn write_split_manifest(
+    path: &Path,
+    root: &Path,
+    assignment: &BTreeMap<PathBuf, String>,
+) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(BufWriter::new(File::create(path)?));
+    writer.write_record(["image", "stratum", "split"])?;
+    for (image, split) in assignment {
+        let relative = image.strip_prefix(root).unwrap_or(image);
+        writer.write_record([
+            relative.display().to_string(),
+            stratum(root, image).display().to_string(),
+            split.clone(),
+        ])?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_parses_what_it_displays() {
+        for sample in [Sample::Count(12), Sample::Fraction(0.25), Sample::Fraction(0.1)] {
+            assert_eq!(sample.to_string().parse::<Sample>(), Ok(sample));
+        }
+        assert!("ten".parse::<Sample>().is_err());
+    }
+
+    fn images(folders: &[(&str, usize)]) -> Vec<PathBuf> {
+        folders
+            .iter()
+            .flat_map(|&(folder, count)| {
+                (0..count).map(move |i| Path::new("data").join(folder).join(format!("{i}.png")))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apportion_rounds_by_largest_remainder() {
+        assert_eq!(apportion(&[1.0, 1.0, 1.0], 10), [4, 3, 3]);
+        assert_eq!(apportion(&[0.8, 0.2], 7), [6, 1]);
+        assert_eq!(apportion(&[6.0, 2.0], 4), [3, 1]);
+        assert_eq!(apportion(&[0.7, 0.2, 0.1], 0), [0, 0, 0]);
+        assert_eq!(apportion(&[0.0, 0.0], 5), [0, 0]);
+        for count in 0..50 {
+            let sizes = apportion(&[0.55, 0.3, 0.15], count);
+            assert_eq!(sizes.iter().sum::<usize>(), count);
+        }
+    }
+
+    #[test]
+    fn stratified_sample_follows_folder_sizes() {
+        let root = Path::new("data");
+        let images = images(&[("a", 6), ("b", 2)]);
+        let sampled = sample(root, &images, Sample::Count(4), 7, true);
+        let in_a = sampled.iter().filter(|image| stratum(root, image) == Path::new("a")).count();
+        assert_eq!((sampled.len(), in_a), (4, 3));
+        // Reproducible, and in the original order.
+        assert_eq!(sample(root, &images, Sample::Count(4), 7, true), sampled);
+        assert!(sampled.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(sample(root, &images, Sample::Fraction(0.5), 1, false).len(), 4);
+        assert_eq!(sample(root, &images, Sample::Count(20), 1, false), images);
+    }
+
+    #[test]
+    fn split_shares_every_folder() {
+        let root = Path::new("data");
+        let images = images(&[("a", 8), ("b", 4)]);
+        let spec: SplitSpec = "analysis=0.75,validation=0.25".parse().unwrap();
+        let splits = split(root, &images, &spec, 3);
+        assert_eq!(splits.len(), images.len());
+        for (folder, expected) in [("a", 2), ("b", 1)] {
+            let validation = splits
+                .iter()
+                .filter(|(image, part)| {
+                    stratum(root, image) == Path::new(folder) && part.as_str() == "validation"
+                })
+                .count();
+            assert_eq!(validation, expected, "{folder}");
+        }
+        assert_eq!(split(root, &images, &spec, 3), splits);
+    }
+
+    #[test]
+    fn split_spec_must_add_up_to_1() {
+        assert!("a=0.5,b=0.4".parse::<SplitSpec>().is_err());
+        assert!("a=0.5,a=0.5".parse::<SplitSpec>().is_err());
+        assert!("a=1.5".parse::<SplitSpec>().is_err());
+        assert!("a=0.5,b=0.5".parse::<SplitSpec>().is_ok());
+    }
+
+    #[test]
+    fn shards_partition_the_dataset() {
+        let root = Path::new("data");
+        let images = images(&[("a", 30), ("b", 10)]);
+        for image in &images {
+            let owners = (1..=4)
+                .filter(|&index| Shard { index, count: 4 }.contains(root, image))
+                .count();
+            assert_eq!(owners, 1, "{}", image.display());
+        }
+    }
+}
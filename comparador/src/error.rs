@@ -0,0 +1,80 @@
+//! Errors of the image pipeline.
+//!
+//! A failure only affects the image, or the image and codec, it happened on:
+//! the run goes on and reports every [`Failure`] at the end.
+use core::fmt;
+use std::{io, path::PathBuf};
+
+use crate::codecs::lossless::LosslessMismatch;
+
+#[derive(Debug)]
+pub enum ComparadorError {
+    Io(io::Error),
+    Decode(String),
+    Encode(String),
+    /// An external program, such as `pngquant`, could not run or failed.
+    ExternalTool {
+        tool: &'static str,
+        reason: String,
+    },
+    /// The decoded image does not have the dimensions of the original.
+    DimensionMismatch {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    /// A lossless codec did not reproduce the original exactly.
+    LosslessMismatch(LosslessMismatch),
+}
+
+impl fmt::Display for ComparadorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComparadorError::Io(error) => write!(f, "I/O error: {error}"),
+            ComparadorError::Decode(reason) => write!(f, "could not decode: {reason}"),
+            ComparadorError::Encode(reason) => write!(f, "could not encode: {reason}"),
+            ComparadorError::ExternalTool { tool, reason } => write!(f, "{tool} failed: {reason}"),
+            ComparadorError::DimensionMismatch { expected, found } => write!(
+                f,
+                "dimensions differ: expected {}x{}, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            ComparadorError::LosslessMismatch(mismatch) => {
+                write!(f, "round-trip is not lossless, {mismatch}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComparadorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ComparadorError::Io(error) => Some(error),
+            ComparadorError::LosslessMismatch(mismatch) => Some(mismatch),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ComparadorError {
+    fn from(error: io::Error) -> Self {
+        ComparadorError::Io(error)
+    }
+}
+
+/// An image that could not be processed, entirely or by a single codec.
+#[derive(Debug)]
+pub struct Failure {
+    pub image: PathBuf,
+    /// [`None`] when the image itself could not be read.
+    pub codec: Option<String>,
+    pub error: ComparadorError,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.codec {
+            Some(codec) => write!(f, "{} [{codec}]: {}", self.image.display(), self.error),
+            None => write!(f, "{}: {}", self.image.display(), self.error),
+        }
+    }
+}
@@ -1,4 +1,10 @@
 pub mod codecs;
+pub mod config;
+pub mod dataset;
+pub mod error;
 pub mod metrics;
+pub mod report;
+pub mod results;
+pub mod summary;
 pub mod traits;
 pub mod utils;
@@ -1,37 +1,137 @@
-#![feature(thread_id_value)]
 #![allow(unused_imports)]
 use comparador::{
-    codecs::{self, Codec},
+    codecs::{self, timing::Repetitions, Codec},
+    config::{Experiment, ExperimentConfig},
+    dataset::{self, manifest, Sample, Shard, SplitSpec},
+    error::{ComparadorError, Failure},
     metrics::{
         self,
         hash::{self, HashMetric, ImageHash},
-        Metric,
+        Metric, WindowedSSIM,
     },
+    report::{self, TableStyle},
+    results::{self, MetricValues, Record, RecordSink, ResultFormat, RunMetadata},
+    summary::{self, Summary},
     traits::Comparison,
-    utils::{RwHashMap, Writer},
 };
 
 use std::{
     cell::{Cell, UnsafeCell},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     io::{self, BufReader, BufWriter, Write},
     num::NonZeroU64,
     ops::Deref,
     path::{Path, PathBuf},
-    sync::{Arc, LazyLock, Mutex, RwLock},
+    process,
+    sync::{mpsc, Arc, LazyLock, Mutex, RwLock},
     thread,
+    time::{Duration, Instant},
 };
 
-use clap::Parser;
-use globwalk::glob;
+use clap::{Args, Parser, Subcommand};
 use image::{DynamicImage, ImageReader};
 use rayon::prelude::*;
 use simple_tqdm::{Config, Tqdm};
 
-#[derive(Parser, Debug, Default)]
+#[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct CliArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs the experiment over a dataset
+    Run(Box<RunArgs>),
+    /// Aggregates a results file per codec
+    Summarize(SummarizeArgs),
+    /// Renders the comparison table of a results file, as in the README
+    Report(ReportArgs),
+    /// Lists the available codecs, metrics and hashes
+    List,
+    /// Dataset utilities
+    #[command(subcommand)]
+    Dataset(DatasetCommand),
+    /// Combines the results files of several shards into one
+    Merge(MergeArgs),
+    /// Prints every metric and hash distance between two images
+    Compare(CompareArgs),
+    /// Encodes one image with some codecs, keeping the encoded files
+    Encode(EncodeArgs),
+}
+
+#[derive(Args, Debug)]
+struct EncodeArgs {
+    /// The image to encode
+    input: PathBuf,
+    /// A codec, by name or glob, e.g. "JPEG (15%)"; may be repeated
+    #[arg(short, long, required = true)]
+    codec: Vec<String>,
+    /// The folder receiving <input name>/<codec>.<extension>
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct CompareArgs {
+    /// The reference image
+    first: PathBuf,
+    /// The image compared to it
+    second: PathBuf,
+    /// Prints JSON instead of a table
+    #[arg(long)]
+    json: bool,
+    /// Saves the windowed SSIM map as a greyscale image
+    #[arg(long)]
+    ssim_map: Option<PathBuf>,
+    /// Only computes the metrics matching one of these names or globs
+    #[arg(long, num_args = 1..)]
+    metrics: Vec<String>,
+    /// Only computes the hashes matching one of these names or globs
+    #[arg(long, num_args = 1..)]
+    hashes: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct MergeArgs {
+    /// The results files to merge
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// The merged results file
+    #[arg(short, long)]
+    output: PathBuf,
+    /// The merged results format [default: guessed from the output extension]
+    #[arg(short, long)]
+    format: Option<ResultFormat>,
+    /// The dataset folder, to detect images missing from every input; sampled
+    /// inputs only expect the images of their sample
+    #[arg(short, long)]
+    dataset: Option<PathBuf>,
+    /// The manifest the shards ran over, relative to the dataset folder
+    #[arg(short, long, requires = "dataset")]
+    manifest: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum DatasetCommand {
+    /// Writes a manifest with the size, SHA-256, dimensions and format of every image
+    Index(IndexArgs),
+}
+
+#[derive(Args, Debug)]
+struct IndexArgs {
+    /// The dataset folder
+    #[arg(short, long)]
+    dataset: PathBuf,
+    /// The manifest file
+    #[arg(short, long, default_value = "manifest.csv")]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
     // Files
     /// The dataset folder
     #[arg(short, long)]
@@ -39,176 +139,669 @@ pub struct CliArgs {
     /// The temp. folder
     #[arg(short, long, default_value_t = String::from("temp"))]
     temp_folder: String,
+    /// TOML file declaring the codecs, metrics and hashes to run
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// The results file
+    #[arg(short, long, default_value = "results.csv")]
+    output: PathBuf,
+    /// The results format [default: guessed from the output extension]
+    #[arg(short, long)]
+    format: Option<ResultFormat>,
+    /// Runs over the images of this manifest, relative to the dataset folder, after verifying them
+    #[arg(short, long)]
+    manifest: Option<PathBuf>,
+    /// Keeps every encoded stream under this folder, as <image path>/<codec>.<extension>,
+    /// the image path including its extension
+    #[arg(long)]
+    keep_artifacts: Option<PathBuf>,
+    /// Appends to the results file, skipping the (image, codec) pairs it already holds
+    #[arg(short, long)]
+    resume: bool,
+    // Selection
+    /// Only runs the codecs matching one of these names or globs, e.g. "JPEG*"
+    #[arg(long, num_args = 1..)]
+    codecs: Vec<String>,
+    /// Only computes the metrics matching one of these names or globs
+    #[arg(long, num_args = 1..)]
+    metrics: Vec<String>,
+    /// Only computes the hashes matching one of these names or globs
+    #[arg(long, num_args = 1..)]
+    hashes: Vec<String>,
+    // Dataset
+    /// Splits the dataset by folder into named fractions, e.g. "analysis=0.8,validation=0.2"
+    #[arg(long)]
+    split: Option<SplitSpec>,
+    /// Where the split is written, as an `image,stratum,split` CSV
+    #[arg(long, default_value = "split.csv")]
+    split_manifest: PathBuf,
+    /// Only runs a random sample of this many images
+    #[arg(long, conflicts_with = "sample_fraction")]
+    sample: Option<usize>,
+    /// Only runs a random sample of this fraction of the images, in [0, 1]
+    #[arg(long, value_parser = parse_fraction)]
+    sample_fraction: Option<f64>,
+    /// Samples each folder in proportion to its size
+    #[arg(long)]
+    stratify: bool,
+    /// Only runs this part of the dataset, e.g. "2/4", so machines can share a run
+    #[arg(long)]
+    shard: Option<Shard>,
+    /// Seed of every random choice over the dataset
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    // Timing
+    /// Timed encode and decode runs per image and codec
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    repeat: u32,
+    /// Untimed runs before the timed ones
+    #[arg(long, default_value_t = 0)]
+    warmup: u32,
 }
 
-static HASHES: LazyLock<Arc<[HashMetric<u64>]>> = LazyLock::new(|| {
-    use comparador::metrics::hash::*;
-    let hash_a = HashMetric::new(String::from("A Hash"), {
-        move |image: &DynamicImage| AHash::hash(image)
-    });
-    let hash_d = HashMetric::new(String::from("D Hash"), {
-        move |image: &DynamicImage| DHash::hash(image)
-    });
-    let hash_p = HashMetric::new(String::from("P Hash"), {
-        move |image: &DynamicImage| PHash::hash(image)
-    });
-    [hash_a, hash_d, hash_p].into_iter().collect()
-});
+#[derive(Args, Debug)]
+struct SummarizeArgs {
+    /// The results file
+    results: PathBuf,
+    /// The results format [default: guessed from the file extension]
+    #[arg(short, long)]
+    format: Option<ResultFormat>,
+    /// Leaves null hash distances and metric values out of the statistics
+    #[arg(short, long)]
+    ignore_zeroes: bool,
+    /// Only keeps the records of this part of the dataset split
+    #[arg(long)]
+    split: Option<String>,
+}
 
-static METRICS: LazyLock<Arc<[Metric<f64>]>> = LazyLock::new(|| {
-    use comparador::metrics::*;
-    let mae = Metric::new(String::from("MAE"), {
-        move |original: &DynamicImage, other: &DynamicImage| MAE::compare(original, other)
-    });
-    let mse = Metric::new(String::from("MSE"), {
-        move |original: &DynamicImage, other: &DynamicImage| MSE::compare(original, other)
-    });
-    let ssim = Metric::new(String::from("SSIM"), {
-        move |original: &DynamicImage, other: &DynamicImage| {
-            SSIM::compare(&original.to_luma16(), &other.to_luma16())
+#[derive(Args, Debug)]
+struct ReportArgs {
+    #[command(flatten)]
+    summarize: SummarizeArgs,
+    /// The table style
+    #[arg(short, long, value_enum, default_value_t = TableStyle::Markdown)]
+    style: TableStyle,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = CliArgs::parse();
+    match args.command {
+        Command::Run(args) => run(*args),
+        Command::Summarize(args) => {
+            print!("{}", load_summary(args)?);
+            Ok(())
         }
-    });
-    let ms_ssim = Metric::new(String::from("MS SSIM"), {
-        move |original: &DynamicImage, outra: &DynamicImage| {
-            MultiScaleSSIM::compare(original, outra)
+        Command::Report(args) => {
+            let summary = load_summary(args.summarize)?;
+            print!("{}", report::table(&summary, args.style));
+            Ok(())
         }
+        Command::List => {
+            list();
+            Ok(())
+        }
+        Command::Dataset(DatasetCommand::Index(args)) => index(args),
+        Command::Merge(args) => merge(args),
+        Command::Compare(args) => compare(args),
+        Command::Encode(args) => encode(args),
+    }
+}
+
+fn encode(args: EncodeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let EncodeArgs {
+        input,
+        codec,
+        output,
+    } = args;
+    let experiment = Experiment::default()
+        .filter(&codec, &[], &[])
+        .unwrap_or_else(|error| {
+            eprintln!("{error}");
+            process::exit(2);
+        });
+    let image = open_image(&input).unwrap_or_else(|error| {
+        eprintln!("{}: {error}", input.display());
+        process::exit(2);
     });
-    let gmsm = Metric::new(String::from("GMSM"), {
-        move |original: &DynamicImage, outra: &DynamicImage| GMSM::compare(original, outra)
-    });
-    let gmsd = Metric::new(String::from("GMSD"), {
-        move |original: &DynamicImage, outra: &DynamicImage| GMSD::compare(original, outra)
-    });
-    [mae, mse, ssim, ms_ssim, gmsm, gmsd].into_iter().collect()
-});
+    let original_size = fs::metadata(&input)?.len();
 
-static CODECS: LazyLock<Arc<[Codec]>> = LazyLock::new(|| {
-    use comparador::codecs::*;
+    let directory = output.join(input.file_stem().unwrap_or_default());
+    fs::create_dir_all(&directory)?;
+    let mut failed = false;
+    for codec in &experiment.codecs {
+        if !codec.supports(&image) {
+            eprintln!("{}: not supported for this image", codec.name());
+            continue;
+        }
+        match codec.encode(&image, &directory.join(".encoding")) {
+            Ok(encoded) => {
+                let path = directory.join(codecs::artifact_name(codec.as_ref()));
+                fs::write(&path, &encoded.stream)?;
+                println!(
+                    "{}: {} ({} bytes, {:.1}% of the original, {:.1} ms)",
+                    codec.name(),
+                    path.display(),
+                    encoded.stream.len(),
+                    100.0 * encoded.stream.len() as f64 / original_size as f64,
+                    encoded.time_spent.as_secs_f64() * 1e3
+                );
+            }
+            Err(error) => {
+                eprintln!("{}: {error}", codec.name());
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
 
-    // Lossless
-    let png = Codec::new(String::from("PNG"), codecs::png);
-    let qoi = Codec::new(String::from("QOI"), codecs::qoi);
-    let webp_lossless = Codec::new(String::from("WEBP"), |image, _| codecs::webp(image, None));
+fn open_image(path: &Path) -> Result<DynamicImage, ComparadorError> {
+    ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|error| ComparadorError::Decode(error.to_string()))
+}
 
-    // Lossy
-    // - 90%
-    let webp_90 = Codec::new(String::from("WEBP (90%)"), |image, _| {
-        codecs::webp(image, Some(90.0))
-    });
-    let jpeg_90 = Codec::new(String::from("JPEG (90%)"), |image, temp_file| {
-        codecs::jpeg(image, temp_file, 90)
-    });
-    let avif_90 = Codec::new(String::from("AVIF (90%)"), |image, temp_file| {
-        codecs::avif(image, temp_file, 90)
-    });
-    // - 80%
-    let webp_80 = Codec::new(String::from("WEBP (80%)"), |image, _| {
-        codecs::webp(image, Some(80.0))
-    });
-    let jpeg_80 = Codec::new(String::from("JPEG (80%)"), |image, temp_file| {
-        codecs::jpeg(image, temp_file, 80)
-    });
-    let avif_80 = Codec::new(String::from("AVIF (80%)"), |image, temp_file| {
-        codecs::avif(image, temp_file, 80)
-    });
-    // - 50%
-    let webp_50 = Codec::new(String::from("WEBP (50%)"), |image, _| {
-        codecs::webp(image, Some(50.0))
-    });
-    let jpeg_50 = Codec::new(String::from("JPEG (50%)"), |image, temp_file| {
-        codecs::jpeg(image, temp_file, 50)
-    });
-    let avif_50 = Codec::new(String::from("AVIF (50%)"), |image, temp_file| {
-        codecs::avif(image, temp_file, 50)
-    });
-    // - 15%
-    let webp_15 = Codec::new(String::from("WEBP (15%)"), |image, _| {
-        codecs::webp(image, Some(15.0))
-    });
-    let jpeg_15 = Codec::new(String::from("JPEG (15%)"), |image, temp_file| {
-        codecs::jpeg(image, temp_file, 15)
-    });
-    let avif_15 = Codec::new(String::from("AVIF (15%)"), |image, temp_file| {
-        codecs::avif(image, temp_file, 15)
-    });
+fn compare(args: CompareArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let CompareArgs {
+        first,
+        second,
+        json,
+        ssim_map,
+        metrics,
+        hashes,
+    } = args;
+    // A single pair: every metric is cheap enough
+    let experiment = Experiment::default()
+        .with_every_metric()
+        .filter(&[], &metrics, &hashes)
+        .unwrap_or_else(|error| {
+            eprintln!("{error}");
+            process::exit(2);
+        });
+    let open = |path: &Path| {
+        open_image(path).unwrap_or_else(|error| {
+            eprintln!("{}: {error}", path.display());
+            process::exit(2);
+        })
+    };
+    let (a, b) = (open(&first), open(&second));
+    let expected = (a.width(), a.height());
+    let found = (b.width(), b.height());
+    if expected != found {
+        eprintln!("{}", ComparadorError::DimensionMismatch { expected, found });
+        process::exit(2);
+    }
 
-    let png_quant_128 = Codec::new(String::from("PNG (pngquant 128)"), |image, temp_file| {
-        codecs::png_quant(image, temp_file, 128)
-    });
-    let png_quant_256 = Codec::new(String::from("PNG (pngquant 256)"), |image, temp_file| {
-        codecs::png_quant(image, temp_file, 256)
-    });
+    if let Some(path) = ssim_map {
+        let (_, map) = WindowedSSIM::default().compute(&a, &b);
+        DynamicImage::from(map).to_luma8().save(&path)?;
+    }
 
-    [
-        png,
-        qoi,
-        webp_lossless,
-        webp_90,
-        webp_80,
-        webp_50,
-        webp_15,
-        jpeg_90,
-        jpeg_80,
-        jpeg_50,
-        jpeg_15,
-        avif_90,
-        avif_80,
-        avif_50,
-        avif_15,
-        png_quant_128,
-        png_quant_256,
-    ]
-    .into_iter()
-    .collect()
-});
+    let metric_values: Vec<(String, f64)> = experiment
+        .metrics
+        .iter()
+        .map(|metric| (metric.name.clone(), metric.apply(&a, &b)))
+        .collect();
+    let hash_distances: Vec<(String, f64)> = experiment
+        .hashes
+        .iter()
+        .map(|hash| (hash.name.clone(), u64::compare(&(hash + &a), &(hash + &b))))
+        .collect();
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = CliArgs::parse();
-    let CliArgs {
+    if json {
+        let metric_values: BTreeMap<String, f64> = metric_values.into_iter().collect();
+        let value = serde_json::json!({
+            "first": first,
+            "second": second,
+            "width": expected.0,
+            "height": expected.1,
+            "metrics": MetricValues(&metric_values),
+            "hashes": hash_distances.into_iter().collect::<BTreeMap<_, _>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    println!("{} vs. {} ({}x{})", first.display(), second.display(), expected.0, expected.1);
+    let width = metric_values
+        .iter()
+        .chain(&hash_distances)
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    println!("Metrics:");
+    for (name, value) in &metric_values {
+        println!("- {name:<width$} : {value:.6}");
+    }
+    println!("Hashes:");
+    for (name, distance) in &hash_distances {
+        println!("- {name:<width$} : {distance:.6}");
+    }
+    Ok(())
+}
+
+fn merge(args: MergeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let MergeArgs {
+        inputs,
+        output,
+        format,
+        dataset,
+        manifest,
+    } = args;
+    // How each input selected its images, if it was sampled
+    let mut samples = vec![];
+    for input in &inputs {
+        let path = RunMetadata::path_for(input);
+        let metadata = if path.exists() {
+            Some(RunMetadata::read(&path)?)
+        } else {
+            None
+        };
+        let sample = match metadata {
+            Some(RunMetadata {
+                sample: Some(sample),
+                seed,
+                stratified_sample,
+                ..
+            }) => {
+                let sample: Sample = sample
+                    .parse()
+                    .map_err(|error| format!("{}: {error}", path.display()))?;
+                Some((sample, seed, stratified_sample))
+            }
+            _ => None,
+        };
+        samples.push(sample);
+    }
+    let mut sources = vec![];
+    for input in inputs {
+        let records = results::read_records(&input, ResultFormat::from_path(&input))?;
+        sources.push((input, records));
+    }
+    let (records, duplicates) = results::merge(sources);
+
+    let mut missing: Vec<PathBuf> = vec![];
+    if let Some(dataset) = dataset {
+        let expected = match manifest {
+            Some(manifest) => manifest::read_manifest(&manifest)?
+                .into_iter()
+                .map(|entry| dataset.join(entry.image))
+                .collect(),
+            None => dataset::find_images(&dataset)?,
+        };
+        // Sampled inputs only expect the images of their sample, drawn again
+        // from their seed; shards are not applied, so a missing one shows.
+        let selected: Option<HashSet<PathBuf>> = samples
+            .iter()
+            .map(|sample| {
+                sample.map(|(sample, seed, stratified)| {
+                    dataset::sample(&dataset, &expected, sample, seed, stratified)
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|samples| samples.into_iter().flatten().collect());
+        let found: HashSet<&PathBuf> = records.iter().map(|record| &record.image).collect();
+        missing = expected
+            .into_iter()
+            .filter(|image| selected.as_ref().is_none_or(|selected| selected.contains(image)))
+            .filter(|image| !found.contains(image))
+            .collect();
+    }
+
+    // Columns of every input, in registry order
+    let ordered = |mut names: Vec<String>, registry: Vec<String>| {
+        names.sort_by_key(|name| {
+            let position = registry.iter().position(|known| known == name);
+            (position.unwrap_or(usize::MAX), name.clone())
+        });
+        names.dedup();
+        names
+    };
+    let metric_names = ordered(
+        records.iter().flat_map(|record| record.metrics.keys().cloned()).collect(),
+        metrics::all().into_iter().map(|metric| metric.name).collect(),
+    );
+    let hash_names = ordered(
+        records.iter().flat_map(|record| record.hashes.keys().cloned()).collect(),
+        hash::all().into_iter().map(|hash| hash.name).collect(),
+    );
+    let format = format.unwrap_or_else(|| ResultFormat::from_path(&output));
+    let mut sink = results::create_sink(&output, format, metric_names, hash_names)?;
+    for record in &records {
+        sink.write(record)?;
+    }
+    sink.flush()?;
+    println!("{} records merged into {}", records.len(), output.display());
+
+    if !duplicates.is_empty() {
+        eprintln!("{} duplicate(s), the first one was kept:", duplicates.len());
+        for duplicate in &duplicates {
+            eprintln!("- {duplicate}");
+        }
+    }
+    if !missing.is_empty() {
+        eprintln!("{} image(s) missing from every input:", missing.len());
+        for image in &missing {
+            eprintln!("- {}", image.display());
+        }
+    }
+    if !duplicates.is_empty() || !missing.is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn parse_fraction(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        Ok(_) => Err(String::from("must be between 0 and 1")),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn index(args: IndexArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let IndexArgs { dataset, output } = args;
+    let images = dataset::find_images(&dataset)?;
+    let (entries, failures) = manifest::index(&dataset, &images);
+    manifest::write_manifest(&output, &entries)?;
+    println!("{} images indexed into {}", entries.len(), output.display());
+
+    if !failures.is_empty() {
+        eprintln!("{} image(s) left out:", failures.len());
+        for failure in &failures {
+            eprintln!("- {failure}");
+        }
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn list() {
+    let experiment = Experiment::default();
+    println!("Codecs:");
+    for codec in &experiment.codecs {
+        let kind = if codec.is_lossy() { "lossy" } else { "lossless" };
+        println!("- {} ({}, {kind})", codec.name(), codec.family());
+    }
+    println!("Metrics (high-cost ones only run when named):");
+    for metric in &metrics::all() {
+        let direction = if metric.higher_is_better {
+            "higher is better"
+        } else {
+            "lower is better"
+        };
+        println!("- {} ({direction}, {} cost)", metric.name, metric.cost);
+    }
+    println!("Hashes:");
+    for hash in &experiment.hashes {
+        println!("- {}", hash.name);
+    }
+}
+
+fn load_summary(args: SummarizeArgs) -> io::Result<Summary> {
+    let SummarizeArgs {
+        results,
+        format,
+        ignore_zeroes,
+        split,
+    } = args;
+    let format = format.unwrap_or_else(|| ResultFormat::from_path(&results));
+    let mut records = results::read_records(&results, format)?;
+    if let Some(split) = split {
+        records.retain(|record| record.split.as_ref() == Some(&split));
+    }
+    Ok(summary::summarize(records, ignore_zeroes))
+}
+
+fn run(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let RunArgs {
         dataset,
         temp_folder,
+        config,
+        output,
+        format,
+        manifest,
+        keep_artifacts,
+        resume,
+        codecs,
+        metrics,
+        hashes,
+        split,
+        split_manifest,
+        sample,
+        sample_fraction,
+        stratify,
+        shard,
+        seed,
+        repeat,
+        warmup,
     } = args;
-    let log_folder: &str = "./logs";
-    let dataset = dataset.to_str().unwrap().to_owned();
-    let dataset = dataset + "/**/*.{avif,bmp,exr,gif,jpeg,jpg,ico,png,pnm,tga,tiff,qoi,webp}";
+    let experiment = match &config {
+        Some(config) => ExperimentConfig::from_file(config)
+            .and_then(ExperimentConfig::build)
+            .unwrap_or_else(|error| {
+                eprintln!("{}: {error}", config.display());
+                process::exit(2);
+            }),
+        None if metrics.is_empty() => Experiment::default(),
+        None => Experiment::default().with_every_metric(),
+    };
+    let experiment = experiment
+        .filter(&codecs, &metrics, &hashes)
+        .unwrap_or_else(|error| {
+            eprintln!("{error}");
+            process::exit(2);
+        });
+    let format = format.unwrap_or_else(|| ResultFormat::from_path(&output));
+    let image_names = match &manifest {
+        Some(manifest) => {
+            let entries = manifest::read_manifest(manifest)?;
+            let mismatches = manifest::verify(&dataset, &entries);
+            if !mismatches.is_empty() {
+                eprintln!(
+                    "{} file(s) differ from {}:",
+                    mismatches.len(),
+                    manifest.display()
+                );
+                for mismatch in &mismatches {
+                    eprintln!("- {mismatch}");
+                }
+                process::exit(2);
+            }
+            entries.into_iter().map(|entry| dataset.join(entry.image)).collect()
+        }
+        None => dataset::find_images(&dataset)?,
+    };
+    if image_names.is_empty() {
+        eprintln!("{}: no images found", dataset.display());
+        process::exit(2);
+    }
+    let sample = sample
+        .map(Sample::Count)
+        .or(sample_fraction.map(Sample::Fraction));
+    let image_names = match sample {
+        Some(sample) => dataset::sample(&dataset, &image_names, sample, seed, stratify),
+        None => image_names,
+    };
+    let mut splits = match &split {
+        Some(spec) => {
+            let splits = dataset::split(&dataset, &image_names, spec, seed);
+            dataset::write_split_manifest(&split_manifest, &dataset, &splits)?;
+            splits
+        }
+        None => BTreeMap::new(),
+    };
+    // Sharded after splitting, so every shard agrees on the split
+    let image_names: Vec<PathBuf> = match shard {
+        Some(shard) => image_names
+            .into_iter()
+            .filter(|image| shard.contains(&dataset, image))
+            .collect(),
+        None => image_names,
+    };
+    if image_names.is_empty() {
+        eprintln!("{}: no images left after sampling and sharding", dataset.display());
+        process::exit(2);
+    }
 
     // Create temp folder if not exists
     fs::create_dir_all(&temp_folder).unwrap_or_default();
-    fs::create_dir_all(&log_folder).unwrap_or_default();
-
-    let mut image_names = vec![];
-    let glob_walker = glob(&dataset)?.filter_map(Result::ok);
-    for entry in glob_walker {
-        // dbg!(&entry);
-        let path = entry.path();
-        if path.is_file() {
-            image_names.push(path.to_path_buf());
+
+    let metric_names = experiment.metrics.iter().map(|m| m.name.clone()).collect();
+    let hash_names = experiment.hashes.iter().map(|h| h.name.clone()).collect();
+    let mut completed: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    let sink = if resume {
+        let (sink, records) = results::resume_sink(&output, format, metric_names, hash_names)
+            .unwrap_or_else(|error| {
+                eprintln!("{}: {error}", output.display());
+                process::exit(2);
+            });
+        for record in records {
+            completed.entry(record.image).or_default().insert(record.codec);
+        }
+        sink
+    } else {
+        results::create_sink(&output, format, metric_names, hash_names)?
+    };
+    let repetitions = Repetitions {
+        repeat: repeat as usize,
+        warmup: warmup as usize,
+    };
+    let mut metadata = RunMetadata {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        dataset: dataset.clone(),
+        manifest,
+        config,
+        images: image_names.len(),
+        seed,
+        sample: sample.map(|sample| sample.to_string()),
+        stratified_sample: sample.is_some() && stratify,
+        split: split.map(|split| split.to_string()),
+        shard: shard.map(|shard| shard.to_string()),
+        repeat: repetitions.repeat,
+        warmup: repetitions.warmup,
+        codecs: experiment.codecs.iter().map(|codec| codec.name()).collect(),
+        metrics: experiment.metrics.iter().map(|m| m.name.clone()).collect(),
+        hashes: experiment.hashes.iter().map(|h| h.name.clone()).collect(),
+        unsupported: BTreeMap::new(),
+        earlier: vec![],
+    };
+    let metadata_path = RunMetadata::path_for(&output);
+    if resume && metadata_path.exists() {
+        let previous = RunMetadata::read(&metadata_path).unwrap_or_else(|error| {
+            eprintln!("{}: {error}", metadata_path.display());
+            process::exit(2);
+        });
+        metadata.resume(previous);
+        for (image, codecs) in &metadata.unsupported {
+            completed.entry(image.clone()).or_default().extend(codecs.iter().cloned());
         }
     }
-    assert_ne!(image_names.len(), 0, "No images found in dataset");
-    let compiled = process_images(image_names, &temp_folder, log_folder);
-    println!("{:?}", compiled);
+    metadata.write(&metadata_path)?;
+    // Every image not completed by a previous run becomes a job
+    let jobs: Vec<Job> = image_names
+        .into_iter()
+        .enumerate()
+        .map(|(index, image)| Job {
+            index,
+            done: completed.remove(&image).unwrap_or_default(),
+            split: splits.remove(&image),
+            artifacts: keep_artifacts.as_ref().map(|directory| {
+                // The extension stays, so photo.png and photo.jpg keep apart
+                directory.join(image.strip_prefix(&dataset).unwrap_or(&image))
+            }),
+            image,
+        })
+        .filter(|job| {
+            !experiment
+                .codecs
+                .iter()
+                .all(|codec| job.done.contains(&codec.name()))
+        })
+        .collect();
+    let failures = process_images(
+        jobs,
+        Arc::new(experiment),
+        repetitions,
+        &temp_folder,
+        sink,
+        metadata,
+        metadata_path,
+    )?;
 
     // Clean temp folder
-    fs::remove_dir(&temp_folder).unwrap();
+    fs::remove_dir(&temp_folder).unwrap_or_default();
 
+    if !failures.is_empty() {
+        eprintln!("{} failure(s):", failures.len());
+        for failure in &failures {
+            eprintln!("- {failure}");
+        }
+        process::exit(1);
+    }
     Ok(())
 }
 
-fn process_images(image_names: Vec<PathBuf>, temp_folder: &str, log_folder: &str) {
-    let _ = LazyLock::force(&HASHES);
-    let _ = LazyLock::force(&METRICS);
-    let _ = LazyLock::force(&CODECS);
+/// One image to process.
+struct Job {
+    /// Position in the dataset, keeping temp. files of images with the same name apart.
+    index: usize,
+    image: PathBuf,
+    /// Codecs already in the results of a resumed run, or unsupported by the image.
+    done: HashSet<String>,
+    split: Option<String>,
+    /// Where the encoded streams are kept, if anywhere.
+    artifacts: Option<PathBuf>,
+}
 
-    let writers: RwHashMap<u8, Writer> = RwLock::new(HashMap::new());
+fn process_images(
+    jobs: Vec<Job>,
+    experiment: Arc<Experiment>,
+    repetitions: Repetitions,
+    temp_folder: &str,
+    mut sink: Box<dyn RecordSink>,
+    mut metadata: RunMetadata,
+    metadata_path: PathBuf,
+) -> io::Result<Vec<Failure>> {
+    // A single writer receives every record of an image at once,
+    // so rows from different images never interleave.
+    let (sender, receiver) = mpsc::channel::<(PathBuf, Vec<Record>, Vec<String>)>();
+    let writer = thread::spawn(move || -> io::Result<()> {
+        let mut written = Instant::now();
+        let mut pending = false;
+        for (image, records, unsupported) in receiver {
+            for record in &records {
+                sink.write(record)?;
+            }
+            // Every finished image is persisted, so an interrupted run can be resumed
+            sink.flush()?;
+            if !unsupported.is_empty() {
+                metadata.unsupported.entry(image).or_default().extend(unsupported);
+                pending = true;
+            }
+            // Whole datasets may be unsupported by a codec: rewritten at most every second
+            if pending && written.elapsed() >= Duration::from_secs(1) {
+                metadata.write(&metadata_path)?;
+                written = Instant::now();
+                pending = false;
+            }
+        }
+        if pending {
+            metadata.write(&metadata_path)?;
+        }
+        Ok(())
+    });
 
+    let failures = Mutex::new(vec![]);
     let temp_folder: Arc<str> = Arc::from(temp_folder);
-    let log_folder: Arc<str> = Arc::from(log_folder);
-    image_names
-        .into_iter()
-        .map(|image_name| {
-            // println!("Processing image: {}", image_name.display());
-            (image_name, HASHES.clone())
+    jobs.into_iter()
+        .map(|job| {
+            // println!("Processing image: {}", job.image.display());
+            (job, experiment.clone(), sender.clone())
         })
         .tqdm_config(
             Config::new()
@@ -217,105 +810,167 @@ fn process_images(image_names: Vec<PathBuf>, temp_folder: &str, log_folder: &str
                 .with_progress_chars("@%#987654321 "),
         )
         .par_bridge()
-        .for_each(|(image_name, hashes)| {
-            // dbg!(&image_name);
-            let temp_folder = temp_folder.clone();
-            let writer: Writer = {
-                let thread_num: u8 = u64::from(thread::current().id().as_u64()) as u8;
-                // dbg!(thread_num);
-
-                if !writers.read().unwrap().contains_key(&thread_num) {
-                    // println!("Writer for thread {thread_num} not found. Creating...");
-                    let log_file = log_folder.to_string() + &format!("/thread-{}.log", thread_num);
-                    let file = fs::File::options()
-                        .read(false)
-                        .write(true)
-                        .create(true)
-                        .open(log_file)
-                        .unwrap();
-                    let writer: Writer = Arc::new(Mutex::new(BufWriter::new(file)));
-                    // println!("Writer for thread {thread_num} successfully created.");
-                    let mut binding_w = writers.write().unwrap();
-                    binding_w.insert(thread_num, writer);
-                    // println!("Writer for thread {thread_num} successfully inserted.");
-                }
-                writers.read().unwrap().get(&thread_num).unwrap().clone()
+        .for_each(|(job, experiment, sender)| {
+            // dbg!(&job.image);
+            // Images from different folders may share a name, the index keeps temp. files apart
+            let temp_file = Path::new(&*temp_folder).join(format!(
+                "{}-{}",
+                job.index,
+                job.image.file_stem().unwrap_or_default().to_string_lossy()
+            ));
+            let processed = match process_image(&job, &experiment, repetitions, &temp_file) {
+                Ok(processed) => processed,
+                Err(error) => Processed {
+                    failures: vec![Failure {
+                        image: job.image.clone(),
+                        codec: None,
+                        error,
+                    }],
+                    ..Default::default()
+                },
             };
-            // dbg!(&writer);
-            process_image(image_name, hashes, &temp_folder, writer).unwrap()
+            if !processed.failures.is_empty() {
+                failures.lock().unwrap().extend(processed.failures);
+            }
+            // The writer only stops early on an I/O error, reported when joining it
+            sender
+                .send((job.image, processed.records, processed.unsupported))
+                .unwrap_or_default();
         });
+    drop(sender);
+
+    writer.join().expect("The results writer panicked")?;
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by(|a, b| (&a.image, &a.codec).cmp(&(&b.image, &b.codec)));
+    Ok(failures)
 }
 
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+/// What became of one image.
+#[derive(Default)]
+struct Processed {
+    records: Vec<Record>,
+    failures: Vec<Failure>,
+    /// Codecs that do not support the image.
+    unsupported: Vec<String>,
+}
+
+/// Runs every codec on one image, collecting the codecs that failed on it and
+/// those that do not support it.
+///
+/// Codecs already done are skipped. Fails as a whole only when the image itself can not be read.
 fn process_image(
-    image_name: PathBuf,
-    hash_metrics: Arc<[HashMetric<u64>]>,
-    temp_folder: &str,
-    w: Writer,
-) -> Result<(), io::Error> {
-    let mut w = w.lock().unwrap();
-    writeln!(w, "Image [{}]", image_name.display())?;
-    let file = fs::File::options().read(true).open(&image_name)?;
-    let original_size = file.metadata().unwrap().len() as f64;
+    job: &Job,
+    experiment: &Experiment,
+    repetitions: Repetitions,
+    temp_file: &Path,
+) -> Result<Processed, ComparadorError> {
+    let image_name = job.image.as_path();
+    let file = fs::File::options().read(true).open(image_name)?;
+    let original_size = file.metadata()?.len();
     let original = ImageReader::new(BufReader::new(file))
-        .with_guessed_format()
-        .unwrap()
+        .with_guessed_format()?
         .decode()
-        .unwrap();
+        .map_err(|error| ComparadorError::Decode(error.to_string()))?;
 
+    let hash_metrics = &experiment.hashes;
     let original_hashes = hash_metrics
         .iter()
-        .map(|format| {
-            let name: &str = &format.name;
-            (name, format + &original)
-        })
-        .collect::<Vec<(&str, u64)>>();
-
-    let codecs = CODECS.clone();
-    for codec in codecs.into_iter() {
-        let temp_file =
-            temp_folder.to_string() + "/" + image_name.file_name().unwrap().to_str().unwrap();
-        let compression = codec.apply(&original, &PathBuf::from(temp_file));
-        if compression.is_none() {
+        .map(|format| format + &original)
+        .collect::<Vec<u64>>();
+
+    let mut records = vec![];
+    let mut failures = vec![];
+    let mut unsupported = vec![];
+    for codec in experiment.codecs.iter() {
+        if job.done.contains(&codec.name()) {
             continue;
         }
-        let compression = compression.unwrap();
+        if !codec.supports(&original) {
+            unsupported.push(codec.name());
+            continue;
+        }
+        let compression = match codec.apply(&original, temp_file, repetitions) {
+            Ok(compression) => compression,
+            Err(error) => {
+                failures.push(Failure {
+                    image: image_name.to_path_buf(),
+                    codec: Some(codec.name()),
+                    error,
+                });
+                continue;
+            }
+        };
+        if let Some(directory) = &job.artifacts {
+            let path = directory.join(codecs::artifact_name(codec.as_ref()));
+            if let Err(error) = fs::create_dir_all(directory)
+                .and_then(|_| fs::write(path, &compression.stream))
+            {
+                failures.push(Failure {
+                    image: image_name.to_path_buf(),
+                    codec: Some(codec.name()),
+                    error: error.into(),
+                });
+            }
+        }
+        // The record is still written, with `lossless_verified` false
+        if let Some(mismatch) = &compression.lossless_mismatch {
+            failures.push(Failure {
+                image: image_name.to_path_buf(),
+                codec: Some(codec.name()),
+                error: ComparadorError::LosslessMismatch(mismatch.clone()),
+            });
+        }
+
+        let mut record = Record {
+            image: image_name.to_path_buf(),
+            codec: codec.name(),
+            family: codec.family().to_owned(),
+            lossy: codec.is_lossy(),
+            parameters: codec
+                .parameters()
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+            width: original.width(),
+            height: original.height(),
+            original_size,
+            stream_size: compression.stream.len() as u64,
+            encode_time_us: micros(compression.encode_time.median),
+            decode_time_us: Some(micros(compression.decode_time.median)),
+            timing_runs: repetitions.repeat as u32,
+            encode_time_min_us: Some(micros(compression.encode_time.min)),
+            encode_time_mad_us: Some(micros(compression.encode_time.mad)),
+            decode_time_min_us: Some(micros(compression.decode_time.min)),
+            decode_time_mad_us: Some(micros(compression.decode_time.mad)),
+            lossless_verified: compression.lossless_verified(),
+            metrics: Default::default(),
+            hashes: Default::default(),
+            split: job.split.clone(),
+        };
 
         if let Some(other) = compression.image_if_lossy {
-            writeln!(
-                w,
-                "Codec,{} (Lossy),{}b,{}mcs,{}%",
-                codec.name,
-                compression.stream_size,
-                compression.time_spent.as_micros(),
-                100.0 * compression.stream_size as f64 / original_size
-            )?;
-
-            for ((hash_name, hash_original), hash_metric) in
-                original_hashes.iter().zip(hash_metrics.iter())
-            {
+            for (hash_original, hash_metric) in original_hashes.iter().zip(hash_metrics.iter()) {
                 let hash_other = hash_metric + &other;
-                writeln!(
-                    w,
-                    "Hash,{},{}%",
-                    hash_name,
-                    100.0 * u64::compare(hash_original, &hash_other)
-                )?;
+                record.hashes.insert(
+                    hash_metric.name.clone(),
+                    u64::compare(hash_original, &hash_other),
+                );
             }
 
-            for metric in METRICS.iter() {
+            for metric in experiment.metrics.iter() {
                 let value = metric.apply(&original, &other);
-                writeln!(w, "Metric,{},{}", metric.name, value)?;
+                record.metrics.insert(metric.name.clone(), value);
             }
-        } else {
-            writeln!(
-                w,
-                "Codec,{} (Lossless),{}b,{}mcs,{}%",
-                codec.name,
-                compression.stream_size,
-                compression.time_spent.as_micros(),
-                100.0 * compression.stream_size as f64 / original_size
-            )?;
         }
+        records.push(record);
     }
-    Ok(())
+    Ok(Processed {
+        records,
+        failures,
+        unsupported,
+    })
 }
@@ -0,0 +1,166 @@
+//! Metrics on single colour channels, so that chroma artifacts, such as those
+//! of 4:2:0 subsampling, are not hidden by the conversion to luma.
+use super::{WindowedSSIM, GMSD};
+use crate::traits::Comparison;
+
+use image::{DynamicImage, ImageBuffer, Luma};
+
+/// Weights of Y, Cb and Cr in the weighted variants, as in the video coding
+/// common test conditions.
+pub const YCBCR_WEIGHTS: [f64; 3] = [6.0 / 8.0, 1.0 / 8.0, 1.0 / 8.0];
+
+/// Full-range BT.601 YCbCr, as in JPEG, with the chroma centered on 0.5.
+pub fn ycbcr([r, g, b]: [f64; 3]) -> [f64; 3] {
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.5 - 0.168736 * r - 0.331264 * g + 0.5 * b,
+        0.5 + 0.5 * r - 0.418688 * g - 0.081312 * b,
+    ]
+}
+
+/// Mean squared error of each channel, after converting both images to RGB and
+/// mapping every pixel through `transform`.
+pub fn channel_mse(
+    original: &DynamicImage,
+    other: &DynamicImage,
+    transform: fn([f64; 3]) -> [f64; 3],
+) -> [f64; 3] {
+    let (original, other) = (original.to_rgb32f(), other.to_rgb32f());
+    let mut sums = [0f64; 3];
+    for (a, b) in original.pixels().zip(other.pixels()) {
+        let a = transform(a.0.map(f64::from));
+        let b = transform(b.0.map(f64::from));
+        for (sum, (a, b)) in sums.iter_mut().zip(a.iter().zip(&b)) {
+            *sum += (a - b).powi(2);
+        }
+    }
+    let len = (original.width() as u64 * original.height() as u64).max(1) as f64;
+    sums.map(|sum| sum / len)
+}
+
+/// Splits the image into 3 greyscale images, one per channel, after mapping
+/// every RGB pixel through `transform`.
+pub fn split(image: &DynamicImage, transform: fn([f64; 3]) -> [f64; 3]) -> [DynamicImage; 3] {
+    let rgb = image.to_rgb32f();
+    let mut planes = [(); 3].map(|_| Vec::with_capacity(rgb.len() / 3));
+    for pixel in rgb.pixels() {
+        let values = transform(pixel.0.map(f64::from));
+        for (plane, value) in planes.iter_mut().zip(values) {
+            plane.push((value.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16);
+        }
+    }
+    planes.map(|plane| {
+        let buffer = ImageBuffer::<Luma<u16>, _>::from_raw(rgb.width(), rgb.height(), plane)
+            .expect("one sample per pixel");
+        DynamicImage::ImageLuma16(buffer)
+    })
+}
+
+/// Applies `metric` to each channel of both images.
+fn per_channel(
+    original: &DynamicImage,
+    other: &DynamicImage,
+    transform: fn([f64; 3]) -> [f64; 3],
+    metric: fn(&DynamicImage, &DynamicImage) -> f64,
+) -> [f64; 3] {
+    let original = split(original, transform);
+    let other = split(other, transform);
+    [0, 1, 2].map(|channel| metric(&original[channel], &other[channel]))
+}
+
+/// Averages Y, Cb and Cr values with [`YCBCR_WEIGHTS`].
+pub fn weighted(values: [f64; 3]) -> f64 {
+    values.iter().zip(YCBCR_WEIGHTS).map(|(value, weight)| value * weight).sum()
+}
+
+pub fn rgb(pixel: [f64; 3]) -> [f64; 3] {
+    pixel
+}
+
+/// [`WindowedSSIM`] of a single RGB channel: 0 for red, 1 for green, 2 for blue.
+pub struct ChannelSSIM<const CHANNEL: usize>;
+
+impl<const CHANNEL: usize> Comparison<DynamicImage> for ChannelSSIM<CHANNEL> {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let original = &split(original, rgb)[CHANNEL];
+        let other = &split(other, rgb)[CHANNEL];
+        WindowedSSIM::compare(original, other)
+    }
+}
+
+/// [`WindowedSSIM`] of Y, Cb and Cr, weighted 6:1:1.
+pub struct YCbCrSSIM;
+
+impl Comparison<DynamicImage> for YCbCrSSIM {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        weighted(per_channel(original, other, ycbcr, WindowedSSIM::compare))
+    }
+}
+
+/// [`GMSD`] of a single RGB channel: 0 for red, 1 for green, 2 for blue.
+pub struct ChannelGMSD<const CHANNEL: usize>;
+
+impl<const CHANNEL: usize> Comparison<DynamicImage> for ChannelGMSD<CHANNEL> {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let original = &split(original, rgb)[CHANNEL];
+        let other = &split(other, rgb)[CHANNEL];
+        GMSD::compare(original, other)
+    }
+}
+
+/// [`GMSD`] of Y, Cb and Cr, weighted 6:1:1.
+pub struct YCbCrGMSD;
+
+impl Comparison<DynamicImage> for YCbCrGMSD {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        weighted(per_channel(original, other, ycbcr, GMSD::compare))
+    }
+}
+
+/// Mean squared error of Cb and Cr only, in `[0, 1]`, ignoring the luma: a
+/// measure of colour bleeding.
+pub struct ChromaMSE;
+
+impl Comparison<DynamicImage> for ChromaMSE {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let [_, cb, cr] = channel_mse(original, other, ycbcr);
+        (cb + cr) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgb, RgbImage};
+
+    fn gradient(size: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(size, size, |x, y| {
+            Rgb([(x * 20) as u8, (y * 20) as u8, ((x + y) * 10) as u8])
+        }))
+    }
+
+    #[test]
+    fn images_too_small_for_gradients_are_not_compared() {
+        for size in [0, 1, 5] {
+            let image = gradient(size);
+            assert!(GMSD::compare(&image, &image).is_nan());
+            assert!(ChannelGMSD::<1>::compare(&image, &image).is_nan());
+            assert!(YCbCrGMSD::compare(&image, &image).is_nan());
+        }
+    }
+
+    #[test]
+    fn identical_images_have_no_gradient_deviation() {
+        let image = gradient(8);
+        assert_eq!(ChannelGMSD::<0>::compare(&image, &image), 0.0);
+        assert_eq!(YCbCrGMSD::compare(&image, &image), 0.0);
+    }
+
+    #[test]
+    fn ycbcr_of_grey_has_centred_chroma() {
+        let [y, cb, cr] = ycbcr([0.25; 3]);
+        assert!((y - 0.25).abs() < 1e-12);
+        assert!((cb - 0.5).abs() < 1e-12 && (cr - 0.5).abs() < 1e-12);
+    }
+}
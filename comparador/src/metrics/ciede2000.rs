@@ -0,0 +1,221 @@
+//! Colour differences in CIELAB, with the CIEDE2000 formula.
+//!
+//! https://hajim.rochester.edu/ece/sites/gsharma/ciede2000/ciede2000noteCRNA.pdf
+use crate::traits::Comparison;
+
+use image::DynamicImage;
+
+/// A CIELAB colour, relative to the D65 white point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// CIE XYZ of the D65 white point.
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+impl Lab {
+    /// Converts an sRGB colour with components in `[0, 1]`.
+    pub fn from_srgb(rgb: [f64; 3]) -> Lab {
+        let [r, g, b] = rgb.map(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let xyz = [
+            0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+            0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+            0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+        ];
+
+        const DELTA: f64 = 6.0 / 29.0;
+        let [fx, fy, fz] = [0, 1, 2].map(|i| {
+            let t = xyz[i] / WHITE[i];
+            if t > DELTA.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        });
+        Lab {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    /// CIEDE2000 colour difference, with unit weighting factors.
+    pub fn delta_e_2000(&self, other: &Lab) -> f64 {
+        let pow7 = |value: f64| value.powi(7);
+        let (l1, a1, b1) = (self.l, self.a, self.b);
+        let (l2, a2, b2) = (other.l, other.a, other.b);
+
+        let mean_chroma = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+        let g = 0.5 * (1.0 - (pow7(mean_chroma) / (pow7(mean_chroma) + pow7(25.0))).sqrt());
+        let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+        let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+        let hue = |b: f64, a: f64| {
+            if a == 0.0 && b == 0.0 {
+                0.0
+            } else {
+                b.atan2(a).to_degrees().rem_euclid(360.0)
+            }
+        };
+        let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+        let delta_l = l2 - l1;
+        let delta_c = c2 - c1;
+        let delta_h = if c1 * c2 == 0.0 {
+            0.0
+        } else if (h2 - h1).abs() <= 180.0 {
+            h2 - h1
+        } else if h2 - h1 > 180.0 {
+            h2 - h1 - 360.0
+        } else {
+            h2 - h1 + 360.0
+        };
+        let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+        let mean_l = (l1 + l2) / 2.0;
+        let mean_c = (c1 + c2) / 2.0;
+        let mean_h = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) / 2.0
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+
+        let cos = |degrees: f64| degrees.to_radians().cos();
+        let t = 1.0 - 0.17 * cos(mean_h - 30.0) + 0.24 * cos(2.0 * mean_h)
+            + 0.32 * cos(3.0 * mean_h + 6.0)
+            - 0.20 * cos(4.0 * mean_h - 63.0);
+        let delta_theta = 30.0 * (-((mean_h - 275.0) / 25.0).powi(2)).exp();
+        let r_c = 2.0 * (pow7(mean_c) / (pow7(mean_c) + pow7(25.0))).sqrt();
+        let s_l = 1.0 + 0.015 * (mean_l - 50.0).powi(2) / (20.0 + (mean_l - 50.0).powi(2)).sqrt();
+        let s_c = 1.0 + 0.045 * mean_c;
+        let s_h = 1.0 + 0.015 * mean_c * t;
+        let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+        let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+        (l * l + c * c + h * h + r_t * c * h).sqrt()
+    }
+}
+
+/// ΔE2000 of every pair of pixels, in row-major order.
+pub fn differences(original: &DynamicImage, other: &DynamicImage) -> Vec<f64> {
+    let (original, other) = (original.to_rgb32f(), other.to_rgb32f());
+    original
+        .pixels()
+        .zip(other.pixels())
+        .map(|(a, b)| {
+            let a = Lab::from_srgb(a.0.map(f64::from));
+            let b = Lab::from_srgb(b.0.map(f64::from));
+            a.delta_e_2000(&b)
+        })
+        .collect()
+}
+
+/// Mean ΔE2000 over the pixels, NaN for empty images.
+pub struct MeanDeltaE;
+
+impl Comparison<DynamicImage> for MeanDeltaE {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let differences = differences(original, other);
+        if differences.is_empty() {
+            return f64::NAN;
+        }
+        differences.iter().sum::<f64>() / differences.len() as f64
+    }
+}
+
+/// 95th percentile of ΔE2000 over the pixels, by the nearest-rank method: the
+/// worst colour shifts, which a mean dilutes on large flat areas. NaN for empty
+/// images.
+pub struct DeltaE95;
+
+impl Comparison<DynamicImage> for DeltaE95 {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let mut differences = differences(original, other);
+        if differences.is_empty() {
+            return f64::NAN;
+        }
+        let rank = ((0.95 * differences.len() as f64).ceil() as usize).max(1) - 1;
+        *differences.select_nth_unstable_by(rank, f64::total_cmp).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test data of Sharma, Wu and Dalal (2005): both colours and their ΔE2000.
+    #[rustfmt::skip]
+    const SHARMA: [[f64; 7]; 34] = [
+        [50.0, 2.6772, -79.7751, 50.0, 0.0, -82.7485, 2.0425],
+        [50.0, 3.1571, -77.2803, 50.0, 0.0, -82.7485, 2.8615],
+        [50.0, 2.8361, -74.0200, 50.0, 0.0, -82.7485, 3.4412],
+        [50.0, -1.3802, -84.2814, 50.0, 0.0, -82.7485, 1.0000],
+        [50.0, -1.1848, -84.8006, 50.0, 0.0, -82.7485, 1.0000],
+        [50.0, -0.9009, -85.5211, 50.0, 0.0, -82.7485, 1.0000],
+        [50.0, 0.0, 0.0, 50.0, -1.0, 2.0, 2.3669],
+        [50.0, -1.0, 2.0, 50.0, 0.0, 0.0, 2.3669],
+        [50.0, 2.49, -0.001, 50.0, -2.49, 0.0009, 7.1792],
+        [50.0, 2.49, -0.001, 50.0, -2.49, 0.0010, 7.1792],
+        [50.0, 2.49, -0.001, 50.0, -2.49, 0.0011, 7.2195],
+        [50.0, 2.49, -0.001, 50.0, -2.49, 0.0012, 7.2195],
+        [50.0, -0.001, 2.49, 50.0, 0.0009, -2.49, 4.8045],
+        [50.0, -0.001, 2.49, 50.0, 0.0010, -2.49, 4.8045],
+        [50.0, -0.001, 2.49, 50.0, 0.0011, -2.49, 4.7461],
+        [50.0, 2.5, 0.0, 50.0, 0.0, -2.5, 4.3065],
+        [50.0, 2.5, 0.0, 73.0, 25.0, -18.0, 27.1492],
+        [50.0, 2.5, 0.0, 61.0, -5.0, 29.0, 22.8977],
+        [50.0, 2.5, 0.0, 56.0, -27.0, -3.0, 31.9030],
+        [50.0, 2.5, 0.0, 58.0, 24.0, 15.0, 19.4535],
+        [50.0, 2.5, 0.0, 50.0, 3.1736, 0.5854, 1.0000],
+        [50.0, 2.5, 0.0, 50.0, 3.2972, 0.0, 1.0000],
+        [50.0, 2.5, 0.0, 50.0, 1.8634, 0.5757, 1.0000],
+        [50.0, 2.5, 0.0, 50.0, 3.2592, 0.3350, 1.0000],
+        [60.2574, -34.0099, 36.2677, 60.4626, -34.1751, 39.4387, 1.2644],
+        [63.0109, -31.0961, -5.8663, 62.8187, -29.7946, -4.0864, 1.2630],
+        [61.2901, 3.7196, -5.3901, 61.4292, 2.2480, -4.9620, 1.8731],
+        [35.0831, -44.1164, 3.7933, 35.0232, -40.0716, 1.5901, 1.8645],
+        [22.7233, 20.0904, -46.6940, 23.0331, 14.9730, -42.5619, 2.0373],
+        [36.4612, 47.8580, 18.3852, 36.2715, 50.5065, 21.2231, 1.4146],
+        [90.8027, -2.0831, 1.4410, 91.1528, -1.6435, 0.0447, 1.4441],
+        [90.9257, -0.5406, -0.9208, 88.6381, -0.8985, -0.7239, 1.5381],
+        [6.7747, -0.2908, -2.4247, 5.8714, -0.0985, -2.2286, 0.6377],
+        [2.0776, 0.0795, -1.1350, 0.9033, -0.0636, -0.5514, 0.9082],
+    ];
+
+    #[test]
+    fn matches_sharma_test_data() {
+        for (index, [l1, a1, b1, l2, a2, b2, expected]) in SHARMA.into_iter().enumerate() {
+            let first = Lab { l: l1, a: a1, b: b1 };
+            let second = Lab { l: l2, a: a2, b: b2 };
+            for found in [first.delta_e_2000(&second), second.delta_e_2000(&first)] {
+                assert!((found - expected).abs() < 1e-4, "pair {}: {found}", index + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn converts_srgb_white_and_black() {
+        let white = Lab::from_srgb([1.0; 3]);
+        assert!((white.l - 100.0).abs() < 1e-3 && white.a.abs() < 1e-3 && white.b.abs() < 1e-3);
+        assert_eq!(Lab::from_srgb([0.0; 3]).l, 0.0);
+    }
+
+    #[test]
+    fn empty_images_are_nan() {
+        let empty = DynamicImage::new_rgb8(0, 0);
+        assert!(MeanDeltaE::compare(&empty, &empty).is_nan());
+        assert!(DeltaE95::compare(&empty, &empty).is_nan());
+    }
+}
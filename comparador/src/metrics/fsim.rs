@@ -0,0 +1,337 @@
+//! FSIM, the feature similarity index of Zhang et al. (2011), on the luma.
+//!
+//! Phase congruency, computed with log-Gabor filters in the frequency domain,
+//! both weighs the pixels and is compared along with the gradient magnitude.
+//!
+//! https://www4.comp.polyu.edu.hk/~cslzhang/IQA/TIP_IQA_FSIM.pdf
+use super::ssim::Plane;
+use crate::traits::Comparison;
+
+use core::f64::consts::PI;
+
+use image::DynamicImage;
+use rustfft::{num_complex::Complex, FftPlanner};
+
+const SCALES: usize = 4;
+const ORIENTATIONS: usize = 4;
+const MIN_WAVELENGTH: f64 = 6.0;
+const MULTIPLIER: f64 = 2.0;
+/// Bandwidth of the log-Gabor filters, as the ratio of the standard deviation
+/// to the centre frequency.
+const SIGMA_ON_F: f64 = 0.55;
+/// Ratio of the angle between orientations to the angular spread.
+const D_THETA_ON_SIGMA: f64 = 1.2;
+/// Standard deviations of noise energy beyond the mean to reject.
+const NOISE_K: f64 = 2.0;
+const EPSILON: f64 = 0.0001;
+
+/// Stabilizing constants of the phase congruency and gradient similarities,
+/// for luma in `[0, 255]`.
+const T1: f64 = 0.85;
+const T2: f64 = 160.0;
+
+/// Normalized frequencies of an axis, in the order of the transform.
+fn frequencies(len: usize) -> Vec<f64> {
+    let centered: Vec<f64> = if len % 2 == 1 {
+        let half = (len - 1) as f64 / 2.0;
+        (0..len).map(|i| (i as f64 - half) / (len as f64 - 1.0).max(1.0)).collect()
+    } else {
+        (0..len).map(|i| (i as f64 - (len / 2) as f64) / len as f64).collect()
+    };
+    // ifftshift
+    let mut shifted = centered;
+    shifted.rotate_left(len / 2);
+    shifted
+}
+
+/// 2D transform of a row-major `width × height` array; the inverse one is
+/// normalized.
+fn fft2(
+    planner: &mut FftPlanner<f64>,
+    data: &mut [Complex<f64>],
+    width: usize,
+    height: usize,
+    inverse: bool,
+) {
+    let (rows, columns) = if inverse {
+        (planner.plan_fft_inverse(width), planner.plan_fft_inverse(height))
+    } else {
+        (planner.plan_fft_forward(width), planner.plan_fft_forward(height))
+    };
+    rows.process(data);
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[y * width + x];
+        }
+        columns.process(&mut column);
+        for (y, value) in column.iter().enumerate() {
+            data[y * width + x] = *value;
+        }
+    }
+    if inverse {
+        let factor = 1.0 / (width * height) as f64;
+        data.iter_mut().for_each(|value| *value *= factor);
+    }
+}
+
+/// Phase congruency of the plane, by Kovesi's method as used in FSIM.
+fn phase_congruency(plane: &Plane) -> Vec<f64> {
+    let (width, height) = (plane.width, plane.height);
+    let len = width * height;
+    let (xs, ys) = (frequencies(width), frequencies(height));
+
+    let mut radius = vec![0.0; len];
+    let mut theta = vec![0.0; len];
+    for (y, &fy) in ys.iter().enumerate() {
+        for (x, &fx) in xs.iter().enumerate() {
+            radius[y * width + x] = fx.hypot(fy);
+            theta[y * width + x] = (-fy).atan2(fx);
+        }
+    }
+    radius[0] = 1.0;
+
+    let log_gabors: Vec<Vec<f64>> = (0..SCALES)
+        .map(|scale| {
+            let frequency = 1.0 / (MIN_WAVELENGTH * MULTIPLIER.powi(scale as i32));
+            let mut filter: Vec<f64> = radius
+                .iter()
+                .map(|&r| {
+                    // Butterworth low-pass of radius 0.45 and order 15.
+                    let low_pass = 1.0 / (1.0 + (r / 0.45).powi(30));
+                    let gabor = (-(r / frequency).ln().powi(2) / (2.0 * SIGMA_ON_F.ln().powi(2)))
+                        .exp();
+                    gabor * low_pass
+                })
+                .collect();
+            filter[0] = 0.0;
+            filter
+        })
+        .collect();
+
+    let mut planner = FftPlanner::new();
+    let mut spectrum: Vec<Complex<f64>> =
+        plane.data.iter().map(|&v| Complex::new(v, 0.0)).collect();
+    fft2(&mut planner, &mut spectrum, width, height, false);
+
+    let theta_sigma = PI / ORIENTATIONS as f64 / D_THETA_ON_SIGMA;
+    let mut energy_all = vec![0.0; len];
+    let mut amplitude_all = vec![0.0; len];
+    for orientation in 0..ORIENTATIONS {
+        let angle = orientation as f64 * PI / ORIENTATIONS as f64;
+        let spread: Vec<f64> = theta
+            .iter()
+            .map(|&theta| {
+                let ds = theta.sin() * angle.cos() - theta.cos() * angle.sin();
+                let dc = theta.cos() * angle.cos() + theta.sin() * angle.sin();
+                (-ds.atan2(dc).powi(2) / (2.0 * theta_sigma * theta_sigma)).exp()
+            })
+            .collect();
+
+        let mut responses: Vec<Vec<Complex<f64>>> = vec![];
+        let mut spatial_filters: Vec<Vec<f64>> = vec![];
+        let mut first_filter_energy = 0.0;
+        for (scale, log_gabor) in log_gabors.iter().enumerate() {
+            let filter: Vec<f64> = log_gabor.iter().zip(&spread).map(|(a, b)| a * b).collect();
+            if scale == 0 {
+                first_filter_energy = filter.iter().map(|v| v * v).sum();
+            }
+            let mut spatial: Vec<Complex<f64>> =
+                filter.iter().map(|&v| Complex::new(v, 0.0)).collect();
+            fft2(&mut planner, &mut spatial, width, height, true);
+            let norm = (len as f64).sqrt();
+            spatial_filters.push(spatial.iter().map(|v| v.re * norm).collect());
+
+            let mut response: Vec<Complex<f64>> =
+                spectrum.iter().zip(&filter).map(|(s, &f)| s * f).collect();
+            fft2(&mut planner, &mut response, width, height, true);
+            responses.push(response);
+        }
+
+        let mut sum_even = vec![0.0; len];
+        let mut sum_odd = vec![0.0; len];
+        for response in &responses {
+            for i in 0..len {
+                sum_even[i] += response[i].re;
+                sum_odd[i] += response[i].im;
+                amplitude_all[i] += response[i].norm();
+            }
+        }
+        let mut energy = vec![0.0; len];
+        for i in 0..len {
+            let x_energy = sum_even[i].hypot(sum_odd[i]) + EPSILON;
+            let (mean_even, mean_odd) = (sum_even[i] / x_energy, sum_odd[i] / x_energy);
+            for response in &responses {
+                let (e, o) = (response[i].re, response[i].im);
+                energy[i] += e * mean_even + o * mean_odd - (e * mean_odd - o * mean_even).abs();
+            }
+        }
+
+        // Noise is estimated from the smallest scale, whose response is mostly
+        // noise: its median energy gives the mean of a Rayleigh distribution.
+        let mut smallest: Vec<f64> = responses[0].iter().map(|v| v.norm_sqr()).collect();
+        let middle = smallest.len() / 2;
+        let median = if smallest.len() % 2 == 1 {
+            *smallest.select_nth_unstable_by(middle, f64::total_cmp).1
+        } else {
+            let upper = *smallest.select_nth_unstable_by(middle, f64::total_cmp).1;
+            let lower = smallest[..middle].iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            (lower + upper) / 2.0
+        };
+        let noise_power = -median / 0.5f64.ln() / first_filter_energy;
+
+        let mut sum_squares = 0.0;
+        let mut sum_products = 0.0;
+        for i in 0..len {
+            for (a, first) in spatial_filters.iter().enumerate() {
+                sum_squares += first[i].powi(2);
+                for second in &spatial_filters[a + 1..] {
+                    sum_products += first[i] * second[i];
+                }
+            }
+        }
+        let noise_energy2 = 2.0 * noise_power * sum_squares + 4.0 * noise_power * sum_products;
+        let tau = (noise_energy2 / 2.0).sqrt();
+        let noise_mean = tau * (PI / 2.0).sqrt();
+        let noise_sigma = ((2.0 - PI / 2.0) * tau * tau).sqrt();
+        let threshold = (noise_mean + NOISE_K * noise_sigma) / 1.7;
+
+        for (total, energy) in energy_all.iter_mut().zip(energy) {
+            *total += (energy - threshold).max(0.0);
+        }
+    }
+
+    energy_all
+        .iter()
+        .zip(&amplitude_all)
+        .map(|(energy, amplitude)| if *amplitude > 0.0 { energy / amplitude } else { 0.0 })
+        .collect()
+}
+
+/// Scharr gradient magnitude, with zeros outside the image.
+fn gradient_magnitude(plane: &Plane) -> Vec<f64> {
+    let (width, height) = (plane.width as isize, plane.height as isize);
+    let at = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            0.0
+        } else {
+            plane.data[(y * width + x) as usize]
+        }
+    };
+    let mut magnitudes = Vec::with_capacity(plane.data.len());
+    for y in 0..height {
+        for x in 0..width {
+            let dx = (3.0 * (at(x + 1, y - 1) - at(x - 1, y - 1))
+                + 10.0 * (at(x + 1, y) - at(x - 1, y))
+                + 3.0 * (at(x + 1, y + 1) - at(x - 1, y + 1)))
+                / 16.0;
+            let dy = (3.0 * (at(x - 1, y + 1) - at(x - 1, y - 1))
+                + 10.0 * (at(x, y + 1) - at(x, y - 1))
+                + 3.0 * (at(x + 1, y + 1) - at(x + 1, y - 1)))
+                / 16.0;
+            magnitudes.push(dx.hypot(dy));
+        }
+    }
+    magnitudes
+}
+
+/// BT.601 luma in `[0, 255]`, averaged over blocks of about 256 / the shorter
+/// side, as in the reference implementation.
+fn luma(image: &DynamicImage) -> Plane {
+    let rgb = image.to_rgb32f();
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let factor = ((width.min(height) as f64 / 256.0).round() as usize).max(1);
+    let luma: Vec<f64> = rgb
+        .pixels()
+        .map(|p| 255.0 * (0.299 * p.0[0] as f64 + 0.587 * p.0[1] as f64 + 0.114 * p.0[2] as f64))
+        .collect();
+    if factor == 1 {
+        return Plane {
+            width,
+            height,
+            data: luma,
+        };
+    }
+
+    // Box filter with zero padding, centred as MATLAB's `conv2(..., 'same')`,
+    // then keeps every `factor`-th sample.
+    let offset = factor / 2;
+    let (new_width, new_height) = (width.div_ceil(factor), height.div_ceil(factor));
+    let mut data = Vec::with_capacity(new_width * new_height);
+    for y in (0..height).step_by(factor) {
+        for x in (0..width).step_by(factor) {
+            let mut sum = 0.0;
+            for sy in (y + offset).saturating_sub(factor - 1)..=(y + offset).min(height - 1) {
+                for sx in (x + offset).saturating_sub(factor - 1)..=(x + offset).min(width - 1) {
+                    sum += luma[sy * width + sx];
+                }
+            }
+            data.push(sum / (factor * factor) as f64);
+        }
+    }
+    Plane {
+        width: new_width,
+        height: new_height,
+        data,
+    }
+}
+
+/// FSIM on the luma, in `[0, 1]`: 1 for identical images. NaN for featureless
+/// ones, with no phase congruency to weigh the comparison.
+pub struct FSIM;
+
+impl Comparison<DynamicImage> for FSIM {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let (x, y) = (luma(original), luma(other));
+        let (pc_x, pc_y) = (phase_congruency(&x), phase_congruency(&y));
+        let (gm_x, gm_y) = (gradient_magnitude(&x), gradient_magnitude(&y));
+
+        let (mut similarity, mut weights) = (0.0, 0.0);
+        for i in 0..pc_x.len() {
+            let (a, b) = (pc_x[i], pc_y[i]);
+            let pc_similarity = (2.0 * a * b + T1) / (a * a + b * b + T1);
+            let (g, h) = (gm_x[i], gm_y[i]);
+            let gradient_similarity = (2.0 * g * h + T2) / (g * g + h * h + T2);
+            let weight = a.max(b);
+            similarity += pc_similarity * gradient_similarity * weight;
+            weights += weight;
+        }
+        if weights == 0.0 {
+            return f64::NAN;
+        }
+        similarity / weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgb, RgbImage};
+
+    fn disc(radius: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(32, 32, |x, y| {
+            let (dx, dy) = (x as i32 - 16, y as i32 - 16);
+            let inside = dx * dx + dy * dy <= (radius * radius) as i32;
+            Rgb(if inside { [220, 180, 40] } else { [20, 40, 90] })
+        }))
+    }
+
+    #[test]
+    fn identical_images_score_1() {
+        let image = disc(8);
+        assert!((FSIM::compare(&image, &image) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn featureless_images_are_not_compared() {
+        let flat = |value| DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 32, Rgb([value; 3])));
+        assert!(FSIM::compare(&flat(40), &flat(200)).is_nan());
+    }
+
+    #[test]
+    fn moved_edges_score_below_1() {
+        let score = FSIM::compare(&disc(8), &disc(10));
+        assert!(score > 0.0 && score < 1.0, "{score}");
+    }
+}
@@ -39,7 +39,7 @@ where
     pub fn new(name: String, func: fn(&DynamicImage) -> Result) -> HashMetric<Result> {
         HashMetric {
             name,
-            func: Arc::new(func.clone()),
+            func: Arc::new(func),
         }
     }
     pub fn apply(&self, image: &DynamicImage) -> Result {
@@ -55,10 +55,25 @@ impl<Result> Add<&DynamicImage> for &HashMetric<Result> {
     }
 }
 
+/// Every perceptual hash known to the comparator, under the names used in the results.
+pub fn all() -> Vec<HashMetric<u64>> {
+    let hash_a = HashMetric::new(String::from("A Hash"), {
+        move |image: &DynamicImage| AHash::hash(image)
+    });
+    let hash_d = HashMetric::new(String::from("D Hash"), {
+        move |image: &DynamicImage| DHash::hash(image)
+    });
+    let hash_p = HashMetric::new(String::from("P Hash"), {
+        move |image: &DynamicImage| PHash::hash(image)
+    });
+    vec![hash_a, hash_d, hash_p]
+}
+
 /// https://www.hackerfactor.com/blog/index.php?/archives/432-Looks-Like-It.html
 pub struct AHash;
 
 impl ImageHash for AHash {
+    #[allow(clippy::needless_range_loop)]
     fn hash(image: &DynamicImage) -> u64 {
         const TAMANHO: usize = 8;
         let luma8 = image
@@ -93,6 +108,7 @@ impl ImageHash for AHash {
 pub struct PHash;
 
 impl ImageHash for PHash {
+    #[allow(clippy::needless_range_loop)]
     fn hash(image: &DynamicImage) -> u64 {
         const TAMANHO: usize = 32;
         const TAMANHO_MENOR: usize = 8;
@@ -130,6 +146,7 @@ impl ImageHash for PHash {
 pub struct DHash;
 
 impl ImageHash for DHash {
+    #[allow(clippy::needless_range_loop)]
     fn hash(image: &DynamicImage) -> u64 {
         const TAMANHO: usize = 8;
         let luma8 = image
@@ -1,17 +1,59 @@
+pub mod channels;
+pub mod ciede2000;
+pub mod fsim;
 pub mod hash;
+pub mod psnr;
+pub mod ssim;
+pub mod ssimulacra2;
+pub mod vif;
+
+pub use channels::{ChannelGMSD, ChannelSSIM, ChromaMSE, YCbCrGMSD, YCbCrSSIM};
+pub use ciede2000::{DeltaE95, MeanDeltaE};
+pub use fsim::FSIM;
+pub use psnr::{ChannelPSNR, LumaPSNR, YCbCrPSNR, PSNR};
+pub use ssim::{MultiScaleSSIM, WindowedSSIM};
+pub use ssimulacra2::Ssimulacra2;
+pub use vif::VIFp;
 
 use crate::{traits::Comparison, utils::gradient_magnitude_similarity};
 
-use core::ops::Add;
+use core::{fmt, ops::Add};
 use std::sync::Arc;
 
 use image::{DynamicImage, GenericImageView, Luma, Pixel, Primitive};
 use num_traits::cast::AsPrimitive;
 
+/// Rough computing cost of a metric, to decide which ones to run on large
+/// datasets: a single pass over the pixels is low, windowed statistics are
+/// medium, and perceptual colour spaces and frequency-domain filters are high.
+/// High-cost metrics only run when asked for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cost {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cost::Low => write!(f, "low"),
+            Cost::Medium => write!(f, "medium"),
+            Cost::High => write!(f, "high"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Metric<Result> {
     pub name: String,
     pub func: Arc<fn(&DynamicImage, &DynamicImage) -> Result>,
+    /// Whether the metric is a similarity (higher is better) rather than an error.
+    pub higher_is_better: bool,
+    /// Unit of unnormalized metrics, such as `dB`; [`None`] for those in `[0, 1]`.
+    pub unit: Option<&'static str>,
+    pub cost: Cost,
 }
 
 impl<Result> Metric<Result>
@@ -21,9 +63,24 @@ where
     pub fn new(name: String, func: fn(&DynamicImage, &DynamicImage) -> Result) -> Metric<Result> {
         Metric {
             name,
-            func: Arc::new(func.clone()),
+            func: Arc::new(func),
+            higher_is_better: false,
+            unit: None,
+            cost: Cost::Low,
         }
     }
+    pub fn higher_is_better(mut self) -> Metric<Result> {
+        self.higher_is_better = true;
+        self
+    }
+    pub fn unit(mut self, unit: &'static str) -> Metric<Result> {
+        self.unit = Some(unit);
+        self
+    }
+    pub fn cost(mut self, cost: Cost) -> Metric<Result> {
+        self.cost = cost;
+        self
+    }
     pub fn apply(&self, original: &DynamicImage, other: &DynamicImage) -> Result {
         (self.func)(original, other)
     }
@@ -37,6 +94,177 @@ impl<Result> Add<(&DynamicImage, &DynamicImage)> for Metric<Result> {
     }
 }
 
+/// Every metric known to the comparator, under the names used in the results.
+pub fn all() -> Vec<Metric<f64>> {
+    let mae = Metric::new(String::from("MAE"), {
+        move |original: &DynamicImage, other: &DynamicImage| MAE::compare(original, other)
+    });
+    let mse = Metric::new(String::from("MSE"), {
+        move |original: &DynamicImage, other: &DynamicImage| MSE::compare(original, other)
+    });
+    let psnr = Metric::new(String::from("PSNR"), {
+        move |original: &DynamicImage, other: &DynamicImage| PSNR::compare(original, other)
+    })
+    .higher_is_better()
+    .unit("dB");
+    let psnr_y = Metric::new(String::from("PSNR Y"), {
+        move |original: &DynamicImage, other: &DynamicImage| LumaPSNR::compare(original, other)
+    })
+    .higher_is_better()
+    .unit("dB");
+    let psnr_r = Metric::new(String::from("PSNR R"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelPSNR::<0>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .unit("dB");
+    let psnr_g = Metric::new(String::from("PSNR G"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelPSNR::<1>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .unit("dB");
+    let psnr_b = Metric::new(String::from("PSNR B"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelPSNR::<2>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .unit("dB");
+    let psnr_ycbcr = Metric::new(String::from("PSNR YCbCr"), {
+        move |original: &DynamicImage, other: &DynamicImage| YCbCrPSNR::compare(original, other)
+    })
+    .higher_is_better()
+    .unit("dB");
+    let ssim = Metric::new(String::from("SSIM"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            SSIM::compare(&original.to_luma16(), &other.to_luma16())
+        }
+    })
+    .higher_is_better();
+    let windowed_ssim = Metric::new(String::from("Windowed SSIM"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            WindowedSSIM::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let windowed_ssim_r = Metric::new(String::from("Windowed SSIM R"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelSSIM::<0>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let windowed_ssim_g = Metric::new(String::from("Windowed SSIM G"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelSSIM::<1>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let windowed_ssim_b = Metric::new(String::from("Windowed SSIM B"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelSSIM::<2>::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let windowed_ssim_ycbcr = Metric::new(String::from("Windowed SSIM YCbCr"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            YCbCrSSIM::compare(original, other)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let ms_ssim = Metric::new(String::from("MS-SSIM"), {
+        move |original: &DynamicImage, outra: &DynamicImage| {
+            MultiScaleSSIM::compare(original, outra)
+        }
+    })
+    .higher_is_better()
+    .cost(Cost::Medium);
+    let tiled_ssim = Metric::new(String::from("Tiled SSIM"), {
+        move |original: &DynamicImage, outra: &DynamicImage| TiledSSIM::compare(original, outra)
+    })
+    .higher_is_better();
+    let gmsm = Metric::new(String::from("GMSM"), {
+        move |original: &DynamicImage, outra: &DynamicImage| GMSM::compare(original, outra)
+    })
+    .higher_is_better();
+    let gmsd = Metric::new(String::from("GMSD"), {
+        move |original: &DynamicImage, outra: &DynamicImage| GMSD::compare(original, outra)
+    });
+    let gmsd_r = Metric::new(String::from("GMSD R"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelGMSD::<0>::compare(original, other)
+        }
+    })
+    .cost(Cost::Medium);
+    let gmsd_g = Metric::new(String::from("GMSD G"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelGMSD::<1>::compare(original, other)
+        }
+    })
+    .cost(Cost::Medium);
+    let gmsd_b = Metric::new(String::from("GMSD B"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChannelGMSD::<2>::compare(original, other)
+        }
+    })
+    .cost(Cost::Medium);
+    let gmsd_ycbcr = Metric::new(String::from("GMSD YCbCr"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            YCbCrGMSD::compare(original, other)
+        }
+    })
+    .cost(Cost::Medium);
+    let chroma_mse = Metric::new(String::from("Chroma MSE"), {
+        move |original: &DynamicImage, other: &DynamicImage| {
+            ChromaMSE::compare(original, other)
+        }
+    });
+    let delta_e = Metric::new(String::from("DeltaE2000"), {
+        move |original: &DynamicImage, other: &DynamicImage| MeanDeltaE::compare(original, other)
+    })
+    .unit("ΔE")
+    .cost(Cost::High);
+    let delta_e_95 = Metric::new(String::from("DeltaE2000 P95"), {
+        move |original: &DynamicImage, other: &DynamicImage| DeltaE95::compare(original, other)
+    })
+    .unit("ΔE")
+    .cost(Cost::High);
+    let ssimulacra2 = Metric::new(String::from("SSIMULACRA2"), {
+        move |original: &DynamicImage, other: &DynamicImage| Ssimulacra2::compare(original, other)
+    })
+    .higher_is_better()
+    .unit("pts")
+    .cost(Cost::High);
+    let vifp = Metric::new(String::from("VIFp"), {
+        move |original: &DynamicImage, other: &DynamicImage| VIFp::compare(original, other)
+    })
+    .higher_is_better()
+    .cost(Cost::High);
+    let fsim = Metric::new(String::from("FSIM"), {
+        move |original: &DynamicImage, other: &DynamicImage| FSIM::compare(original, other)
+    })
+    .higher_is_better()
+    .cost(Cost::High);
+    vec![
+        mae, mse, chroma_mse, psnr, psnr_y, psnr_r, psnr_g, psnr_b, psnr_ycbcr, ssim, windowed_ssim,
+        windowed_ssim_r, windowed_ssim_g, windowed_ssim_b, windowed_ssim_ycbcr, ms_ssim, tiled_ssim,
+        gmsm, gmsd, gmsd_r, gmsd_g, gmsd_b, gmsd_ycbcr, delta_e, delta_e_95,
+        ssimulacra2, vifp, fsim,
+    ]
+}
+
+/// The metrics run when none are named: every one but the high-cost ones.
+pub fn defaults() -> Vec<Metric<f64>> {
+    all().into_iter().filter(|metric| metric.cost < Cost::High).collect()
+}
+
 pub struct MAE;
 
 impl<ImageType, PixelType, SubPixelType> Comparison<ImageType> for MAE
@@ -62,28 +290,13 @@ where
     }
 }
 
+/// Mean squared error over R, G and B, in `[0, 1]`: the error [`PSNR`] is
+/// computed from.
 pub struct MSE;
 
-impl<ImageType, PixelType, SubPixelType> Comparison<ImageType> for MSE
-where
-    ImageType: GenericImageView<Pixel = PixelType>,
-    PixelType: Pixel<Subpixel = SubPixelType>,
-    SubPixelType: AsPrimitive<f64> + Primitive,
-{
-    fn compare(original: &ImageType, other: &ImageType) -> f64 {
-        let sum = original
-            .pixels()
-            .zip(other.pixels())
-            .map(|((.., a), (.., b))| {
-                a.channels()
-                    .iter()
-                    .zip(b.channels())
-                    .map(|(&b, &a)| (b.as_() - a.as_()).powi(2))
-                    .sum::<f64>()
-            })
-            .sum::<f64>();
-        sum / ((original.width() * original.height()) as f64
-            * SubPixelType::DEFAULT_MAX_VALUE.as_().powi(2))
+impl Comparison<DynamicImage> for MSE {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        channels::channel_mse(original, other, channels::rgb).iter().sum::<f64>() / 3.0
     }
 }
 
@@ -129,9 +342,11 @@ where
     }
 }
 
-pub struct MultiScaleSSIM;
+/// Global [`SSIM`] averaged over 8×8 tiles, at a single scale. Formerly
+/// reported as "MS SSIM", as in the README tables.
+pub struct TiledSSIM;
 
-impl Comparison<DynamicImage> for MultiScaleSSIM {
+impl Comparison<DynamicImage> for TiledSSIM {
     fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
         let original = original.to_luma16();
         let other = other.to_luma16();
@@ -162,6 +377,8 @@ impl Comparison<DynamicImage> for MultiScaleSSIM {
 }
 
 /// https://arxiv.org/pdf/1308.3052
+///
+/// NaN for images under 6×6, too small to compare gradients.
 pub struct GMSM;
 
 impl Comparison<DynamicImage> for GMSM {
@@ -172,6 +389,8 @@ impl Comparison<DynamicImage> for GMSM {
 }
 
 /// https://arxiv.org/pdf/1308.3052
+///
+/// NaN for images under 6×6, too small to compare gradients.
 pub struct GMSD;
 
 impl Comparison<DynamicImage> for GMSD {
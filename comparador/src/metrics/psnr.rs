@@ -0,0 +1,117 @@
+//! PSNR, from the mean squared error of samples normalized to `[0, 1]`.
+//!
+//! Every variant is infinite for identical images.
+use super::{
+    channels::{channel_mse, rgb, weighted, ycbcr},
+    MSE,
+};
+use crate::traits::Comparison;
+
+use image::DynamicImage;
+
+/// Peak signal-to-noise ratio, in dB, of a mean squared error over samples in
+/// `[0, 1]`.
+pub fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    }
+}
+
+/// PSNR over the R, G and B channels together, from their [`MSE`].
+pub struct PSNR;
+
+impl Comparison<DynamicImage> for PSNR {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        psnr(MSE::compare(original, other))
+    }
+}
+
+/// PSNR of a single RGB channel: 0 for red, 1 for green, 2 for blue.
+pub struct ChannelPSNR<const CHANNEL: usize>;
+
+impl<const CHANNEL: usize> Comparison<DynamicImage> for ChannelPSNR<CHANNEL> {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        psnr(channel_mse(original, other, rgb)[CHANNEL])
+    }
+}
+
+/// PSNR of the luma, Y in [`ycbcr`].
+pub struct LumaPSNR;
+
+impl Comparison<DynamicImage> for LumaPSNR {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        psnr(channel_mse(original, other, ycbcr)[0])
+    }
+}
+
+/// PSNR of the Y, Cb and Cr errors, weighted 6:1:1 before taking the PSNR, so
+/// it stays finite when only some channels are identical, such as the chroma
+/// of greyscale images.
+pub struct YCbCrPSNR;
+
+impl Comparison<DynamicImage> for YCbCrPSNR {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        psnr(weighted(channel_mse(original, other, ycbcr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn constant(value: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb(value)))
+    }
+
+    #[test]
+    fn psnr_of_a_known_error() {
+        let (a, b) = (constant([128; 3]), constant([153; 3]));
+        let mse = (25.0f64 / 255.0).powi(2);
+        assert!((MSE::compare(&a, &b) - mse).abs() < 1e-6);
+        assert!((PSNR::compare(&a, &b) - 20.172).abs() < 1e-3);
+        assert!((LumaPSNR::compare(&a, &b) - 20.172).abs() < 1e-3);
+    }
+
+    #[test]
+    fn psnr_is_computed_from_mse() {
+        let a = constant([10, 200, 30]);
+        let b = constant([12, 190, 60]);
+        let mse = MSE::compare(&a, &b);
+        assert!((PSNR::compare(&a, &b) + 10.0 * mse.log10()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn alpha_does_not_dilute_the_error() {
+        let rgba = |value| DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba(value)));
+        let (a, b) = (rgba([128, 128, 128, 255]), rgba([153, 153, 153, 255]));
+        assert_eq!(MSE::compare(&a, &b), MSE::compare(&constant([128; 3]), &constant([153; 3])));
+    }
+
+    #[test]
+    fn identical_images_have_infinite_psnr() {
+        let a = constant([1, 2, 3]);
+        assert_eq!(PSNR::compare(&a, &a), f64::INFINITY);
+        assert_eq!(YCbCrPSNR::compare(&a, &a), f64::INFINITY);
+    }
+
+    #[test]
+    fn ycbcr_psnr_weights_the_errors() {
+        let (a, b) = (constant([200, 30, 40]), constant([190, 50, 30]));
+        let [y, cb, cr] = channel_mse(&a, &b, ycbcr);
+        let expected = psnr((6.0 * y + cb + cr) / 8.0);
+        assert!((YCbCrPSNR::compare(&a, &b) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ycbcr_psnr_is_finite_on_greyscale() {
+        let grey = |value| DynamicImage::ImageLuma8(GrayImage::from_pixel(8, 8, Luma([value])));
+        let (a, b) = (grey(128), grey(153));
+        // Only the luma differs, and it carries 6/8 of the error.
+        let expected = psnr(0.75 * (25.0f64 / 255.0).powi(2));
+        assert!((YCbCrPSNR::compare(&a, &b) - expected).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,370 @@
+//! SSIM over a sliding Gaussian window, as in Wang et al. (2004), and its
+//! multi-scale extension (Wang, Simoncelli and Bovik, 2003).
+//!
+//! https://www.cns.nyu.edu/pub/eero/wang03-reprint.pdf
+//! https://www.cns.nyu.edu/pub/eero/wang03b.pdf
+use crate::traits::Comparison;
+
+use image::{DynamicImage, ImageBuffer, Luma};
+
+pub type SsimMap = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// Windowed SSIM on the luma of both images, in `[-1, 1]`: 1 for identical
+/// images, negative for anti-correlated structures.
+///
+/// As in the reference implementation, only windows fully inside the image are
+/// used, so the map is `window_size - 1` pixels smaller on each axis; images
+/// smaller than the window shrink it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowedSSIM {
+    pub k1: f64,
+    pub k2: f64,
+    /// Side of the square window, in pixels.
+    pub window_size: usize,
+    /// Standard deviation of the Gaussian weights, in pixels.
+    pub sigma: f64,
+}
+
+impl Default for WindowedSSIM {
+    fn default() -> Self {
+        WindowedSSIM {
+            k1: 0.01,
+            k2: 0.03,
+            window_size: 11,
+            sigma: 1.5,
+        }
+    }
+}
+
+/// A single channel image of `f64` samples, in row-major order.
+#[derive(Clone, Debug)]
+pub(crate) struct Plane {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f64>,
+}
+
+impl Plane {
+    pub fn luma(image: &DynamicImage) -> Plane {
+        let luma = image.to_luma32f();
+        Plane {
+            width: luma.width() as usize,
+            height: luma.height() as usize,
+            data: luma.into_raw().into_iter().map(f64::from).collect(),
+        }
+    }
+
+    /// Averages 2×2 blocks, halving both sides.
+    fn downsample(&self) -> Plane {
+        let width = self.width / 2;
+        let height = self.height / 2;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let top = &self.data[2 * y * self.width..];
+            let bottom = &self.data[(2 * y + 1) * self.width..];
+            data.extend((0..width).map(|x| {
+                (top[2 * x] + top[2 * x + 1] + bottom[2 * x] + bottom[2 * x + 1]) / 4.0
+            }));
+        }
+        Plane {
+            width,
+            height,
+            data,
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.data.iter().sum::<f64>() / self.data.len() as f64
+    }
+
+    pub fn map(&self, other: &Plane, func: impl Fn(f64, f64) -> f64) -> Plane {
+        Plane {
+            width: self.width,
+            height: self.height,
+            data: self
+                .data
+                .iter()
+                .zip(&other.data)
+                .map(|(&a, &b)| func(a, b))
+                .collect(),
+        }
+    }
+
+    /// Separable convolution keeping only the fully covered positions; empty
+    /// when the plane is smaller than the kernel.
+    pub fn filter_valid(&self, kernel: &[f64]) -> Plane {
+        let size = kernel.len().max(1);
+        let width = (self.width + 1).saturating_sub(size);
+        let height = (self.height + 1).saturating_sub(size);
+        if width == 0 || height == 0 {
+            return Plane {
+                width,
+                height,
+                data: vec![],
+            };
+        }
+
+        let mut rows = Vec::with_capacity(width * self.height);
+        for row in self.data.chunks_exact(self.width) {
+            rows.extend(row.windows(size).map(|window| {
+                window.iter().zip(kernel).map(|(a, b)| a * b).sum::<f64>()
+            }));
+        }
+        let mut data = vec![0.0; width * height];
+        for y in 0..height {
+            for (k, weight) in kernel.iter().enumerate() {
+                let source = &rows[(y + k) * width..(y + k + 1) * width];
+                for (target, value) in data[y * width..(y + 1) * width].iter_mut().zip(source) {
+                    *target += weight * value;
+                }
+            }
+        }
+        Plane {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+/// Normalized 1D Gaussian; its outer product is the 2D window.
+pub(crate) fn gaussian(size: usize, sigma: f64) -> Vec<f64> {
+    let center = (size as f64 - 1.0) / 2.0;
+    let weights: Vec<f64> = (0..size)
+        .map(|i| (-(i as f64 - center).powi(2) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+/// Local statistics of a pair of planes under the window.
+pub(crate) struct LocalStatistics {
+    pub mean_x: Plane,
+    pub mean_y: Plane,
+    pub variance_x: Plane,
+    pub variance_y: Plane,
+    pub covariance: Plane,
+}
+
+impl WindowedSSIM {
+    pub(crate) fn window(&self, width: usize, height: usize) -> Vec<f64> {
+        let size = self.window_size.min(width).min(height).max(1);
+        gaussian(size, self.sigma)
+    }
+
+    /// Stabilizing constants for samples in `[0, 1]`.
+    pub(crate) fn constants(&self) -> (f64, f64) {
+        (self.k1.powi(2), self.k2.powi(2))
+    }
+
+    pub(crate) fn statistics(&self, x: &Plane, y: &Plane) -> LocalStatistics {
+        let window = self.window(x.width, x.height);
+        let mean_x = x.filter_valid(&window);
+        let mean_y = y.filter_valid(&window);
+        let moment = |a: &Plane, b: &Plane, mean_a: &Plane, mean_b: &Plane| {
+            a.map(b, |a, b| a * b)
+                .filter_valid(&window)
+                .map(&mean_a.map(mean_b, |a, b| a * b), |moment, means| {
+                    moment - means
+                })
+        };
+        LocalStatistics {
+            variance_x: moment(x, x, &mean_x, &mean_x),
+            variance_y: moment(y, y, &mean_y, &mean_y),
+            covariance: moment(x, y, &mean_x, &mean_y),
+            mean_x,
+            mean_y,
+        }
+    }
+
+    pub(crate) fn plane_map(&self, x: &Plane, y: &Plane) -> Plane {
+        let (c1, c2) = self.constants();
+        let LocalStatistics {
+            mean_x,
+            mean_y,
+            variance_x,
+            variance_y,
+            covariance,
+        } = self.statistics(x, y);
+        let data = (0..mean_x.data.len())
+            .map(|i| {
+                let (mx, my) = (mean_x.data[i], mean_y.data[i]);
+                (2.0 * mx * my + c1) * (2.0 * covariance.data[i] + c2)
+                    / ((mx * mx + my * my + c1) * (variance_x.data[i] + variance_y.data[i] + c2))
+            })
+            .collect();
+        Plane {
+            data,
+            ..mean_x
+        }
+    }
+
+    /// Mean SSIM index and the SSIM map it averages.
+    pub fn compute(&self, original: &DynamicImage, other: &DynamicImage) -> (f64, SsimMap) {
+        let map = self.plane_map(&Plane::luma(original), &Plane::luma(other));
+        let mean = map.data.iter().sum::<f64>() / map.data.len() as f64;
+        let buffer = SsimMap::from_raw(
+            map.width as u32,
+            map.height as u32,
+            map.data.iter().map(|&value| value as f32).collect(),
+        )
+        .expect("the map holds one sample per pixel");
+        (mean, buffer)
+    }
+}
+
+impl Comparison<DynamicImage> for WindowedSSIM {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        WindowedSSIM::default().compute(original, other).0
+    }
+}
+
+/// Exponents of the 5 scales, from the finest to the coarsest.
+pub const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// MS-SSIM: contrast and structure are compared at every scale, luminance only
+/// at the coarsest one, each scale being low-passed and downsampled by 2.
+///
+/// Images too small for every scale use the ones that fit, with their
+/// exponents renormalized. Negative contrast-structure terms are clamped to 0.
+/// Empty images are NaN.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiScaleSSIM {
+    pub ssim: WindowedSSIM,
+    /// One exponent per scale, from the finest to the coarsest.
+    pub weights: Vec<f64>,
+}
+
+impl Default for MultiScaleSSIM {
+    fn default() -> Self {
+        MultiScaleSSIM {
+            ssim: WindowedSSIM::default(),
+            weights: MS_SSIM_WEIGHTS.to_vec(),
+        }
+    }
+}
+
+impl MultiScaleSSIM {
+    pub fn compute(&self, original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let (c1, c2) = self.ssim.constants();
+        let mut x = Plane::luma(original);
+        let mut y = Plane::luma(other);
+        if x.data.is_empty() {
+            return f64::NAN;
+        }
+
+        let mut terms: Vec<(f64, f64)> = vec![];
+        let mut luminance = 1.0;
+        for (scale, &weight) in self.weights.iter().enumerate() {
+            if scale > 0 {
+                if x.width < 2 || x.height < 2 {
+                    break;
+                }
+                x = x.downsample();
+                y = y.downsample();
+            }
+            let statistics = self.ssim.statistics(&x, &y);
+            let variances = statistics.variance_x.map(&statistics.variance_y, |a, b| a + b);
+            let contrast_structure = statistics
+                .covariance
+                .map(&variances, |cov, var| (2.0 * cov + c2) / (var + c2))
+                .mean();
+            terms.push((contrast_structure.max(0.0), weight));
+            luminance = statistics
+                .mean_x
+                .map(&statistics.mean_y, |a, b| (2.0 * a * b + c1) / (a * a + b * b + c1))
+                .mean();
+        }
+
+        let total: f64 = terms.iter().map(|(_, weight)| weight).sum();
+        let last = terms.last().map_or(0.0, |(_, weight)| *weight);
+        terms
+            .iter()
+            .map(|(value, weight)| value.powf(weight / total))
+            .product::<f64>()
+            * luminance.max(0.0).powf(last / total)
+    }
+}
+
+impl Comparison<DynamicImage> for MultiScaleSSIM {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        MultiScaleSSIM::default().compute(original, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GrayImage, Luma, RgbImage};
+
+    fn constant(width: u32, height: u32, value: u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_pixel(width, height, Luma([value])))
+    }
+
+    /// SSIM of two flat images, whose variances and covariance are 0.
+    fn flat_ssim(a: u8, b: u8) -> f64 {
+        let (c1, c2) = WindowedSSIM::default().constants();
+        let (a, b) = (a as f64 / 255.0, b as f64 / 255.0);
+        (2.0 * a * b + c1) * c2 / ((a * a + b * b + c1) * c2)
+    }
+
+    #[test]
+    fn flat_images_only_differ_in_luminance() {
+        let (a, b) = (constant(64, 64, 128), constant(64, 64, 153));
+        let ssim = WindowedSSIM::compare(&a, &b);
+        // Luma goes through `f32`.
+        assert!((ssim - flat_ssim(128, 153)).abs() < 1e-7);
+        assert!((ssim - 0.984296).abs() < 1e-6);
+        // Contrast and structure are 1 at every scale: only the coarsest
+        // luminance term is left.
+        let ms_ssim = MultiScaleSSIM::default().compute(&a, &b);
+        let exponent = MS_SSIM_WEIGHTS[4] / MS_SSIM_WEIGHTS.iter().sum::<f64>();
+        assert!((ms_ssim - flat_ssim(128, 153).powf(exponent)).abs() < 1e-7);
+    }
+
+    #[test]
+    fn inverted_structure_scores_below_0() {
+        let stripes = |phase: u32| {
+            let image = RgbImage::from_fn(32, 32, |x, _| {
+                image::Rgb([if (x + phase).is_multiple_of(2) { 40 } else { 215 }; 3])
+            });
+            DynamicImage::ImageRgb8(image)
+        };
+        let ssim = WindowedSSIM::compare(&stripes(0), &stripes(1));
+        assert!((-1.0..0.0).contains(&ssim), "{ssim}");
+        assert!((WindowedSSIM::compare(&stripes(0), &stripes(0)) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn filter_valid_handles_planes_smaller_than_the_kernel() {
+        let kernel = gaussian(11, 1.5);
+        for (width, height) in [(0, 0), (0, 20), (20, 0), (5, 20)] {
+            let plane = Plane {
+                width,
+                height,
+                data: vec![0.5; width * height],
+            };
+            assert!(plane.filter_valid(&kernel).data.is_empty());
+        }
+        let plane = Plane {
+            width: 12,
+            height: 11,
+            data: vec![0.5; 12 * 11],
+        };
+        let empty = constant(0, 5, 10);
+        assert!(WindowedSSIM::compare(&empty, &empty).is_nan());
+        let filtered = plane.filter_valid(&kernel);
+        assert_eq!((filtered.width, filtered.height), (2, 1));
+        assert!(filtered.data.iter().all(|value| (value - 0.5).abs() < 1e-12));
+    }
+
+    #[test]
+    fn multi_scale_ssim_of_empty_images_is_nan() {
+        let empty = constant(0, 5, 10);
+        assert!(MultiScaleSSIM::compare(&empty, &empty).is_nan());
+        // A single pixel still fits the shrunk window at the finest scale.
+        let pixel = constant(1, 1, 10);
+        assert!((MultiScaleSSIM::compare(&pixel, &pixel) - 1.0).abs() < 1e-12);
+    }
+}
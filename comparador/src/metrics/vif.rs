@@ -0,0 +1,137 @@
+//! Pixel-domain visual information fidelity (VIFp) of Sheikh and Bovik (2006).
+//!
+//! https://live.ece.utexas.edu/publications/2004/hrs_ieeetip_2004_imginfo.pdf
+use super::ssim::{gaussian, Plane};
+use crate::traits::Comparison;
+
+use image::DynamicImage;
+
+/// Variance of the noise of the visual channel, for luma in `[0, 255]`.
+const SIGMA_N_SQ: f64 = 2.0;
+const EPSILON: f64 = 1e-10;
+
+/// Keeps every other sample of every other row.
+fn decimate(plane: &Plane) -> Plane {
+    let (width, height) = (plane.width.div_ceil(2), plane.height.div_ceil(2));
+    let data = plane
+        .data
+        .chunks_exact(plane.width)
+        .step_by(2)
+        .flat_map(|row| row.iter().step_by(2).copied())
+        .collect();
+    Plane {
+        width,
+        height,
+        data,
+    }
+}
+
+/// VIFp on the luma: the information the distorted image keeps about the
+/// original, relative to the information in the original, over 4 scales.
+///
+/// 1 for identical images, lower for lossy ones; contrast enhancement can take
+/// it above 1. Scales larger than the image are left out; images under 17×17
+/// and flat originals, which carry no information, are not compared: NaN.
+pub struct VIFp;
+
+impl Comparison<DynamicImage> for VIFp {
+    fn compare(original: &DynamicImage, other: &DynamicImage) -> f64 {
+        let luma = |image: &DynamicImage| {
+            let mut plane = Plane::luma(image);
+            plane.data.iter_mut().for_each(|value| *value *= 255.0);
+            plane
+        };
+        let (mut x, mut y) = (luma(original), luma(other));
+
+        let (mut numerator, mut denominator) = (0.0, 0.0);
+        for scale in 1..=4 {
+            let size = (1 << (5 - scale)) + 1;
+            let window = gaussian(size, size as f64 / 5.0);
+            if scale > 1 {
+                x = decimate(&x.filter_valid(&window));
+                y = decimate(&y.filter_valid(&window));
+            }
+            if x.width < size || x.height < size {
+                break;
+            }
+
+            let mean_x = x.filter_valid(&window);
+            let mean_y = y.filter_valid(&window);
+            let moment = |a: &Plane, b: &Plane, mean_a: &Plane, mean_b: &Plane| {
+                a.map(b, |a, b| a * b)
+                    .filter_valid(&window)
+                    .map(&mean_a.map(mean_b, |a, b| a * b), |moment, means| moment - means)
+            };
+            let variance_x = moment(&x, &x, &mean_x, &mean_x);
+            let variance_y = moment(&y, &y, &mean_y, &mean_y);
+            let covariance = moment(&x, &y, &mean_x, &mean_y);
+
+            for i in 0..mean_x.data.len() {
+                let sigma_x = variance_x.data[i].max(0.0);
+                let sigma_y = variance_y.data[i].max(0.0);
+                let sigma_xy = covariance.data[i];
+
+                // Gain and additive noise of the distortion channel.
+                let mut gain = sigma_xy / (sigma_x + EPSILON);
+                let mut noise = sigma_y - gain * sigma_xy;
+                let sigma_x = if sigma_x < EPSILON {
+                    gain = 0.0;
+                    noise = sigma_y;
+                    0.0
+                } else {
+                    sigma_x
+                };
+                if sigma_y < EPSILON {
+                    gain = 0.0;
+                    noise = 0.0;
+                }
+                if gain < 0.0 {
+                    noise = sigma_y;
+                    gain = 0.0;
+                }
+                let noise = noise.max(EPSILON);
+
+                numerator += (1.0 + gain * gain * sigma_x / (noise + SIGMA_N_SQ)).log10();
+                denominator += (1.0 + sigma_x / SIGMA_N_SQ).log10();
+            }
+        }
+        if denominator == 0.0 {
+            return f64::NAN;
+        }
+        numerator / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgb, RgbImage};
+
+    fn checkerboard(contrast: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(40, 40, |x, y| {
+            let value = 128 + if (x / 3 + y / 5) % 2 == 0 { contrast } else { 0 };
+            Rgb([value, value / 2, 255 - value])
+        }))
+    }
+
+    #[test]
+    fn identical_images_score_1() {
+        let image = checkerboard(60);
+        assert!((VIFp::compare(&image, &image) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flat_or_small_originals_are_not_compared() {
+        let flat = DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 40, Rgb([90; 3])));
+        assert!(VIFp::compare(&flat, &checkerboard(60)).is_nan());
+        let small = checkerboard(60).crop_imm(0, 0, 16, 16);
+        assert!(VIFp::compare(&small, &small.brighten(30)).is_nan());
+    }
+
+    #[test]
+    fn lost_contrast_scores_below_1() {
+        let score = VIFp::compare(&checkerboard(60), &checkerboard(20));
+        assert!(score > 0.0 && score < 1.0, "{score}");
+    }
+}
@@ -0,0 +1,317 @@
+//! Comparison tables in the style of the README, as Markdown or as a LaTeX `tabular`.
+//!
+//! Per column, the 3 best values are coloured cyan and the 3 worst grey, except for the
+//! relative size, where every value above 100% is grey instead.
+use crate::{
+    codecs::AvifCodec,
+    metrics,
+    summary::{round_to_deviation, CodecSummary, Statistic, Summary},
+};
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TableStyle {
+    Markdown,
+    Latex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Highlight {
+    Best,
+    Worst,
+}
+
+impl Highlight {
+    fn color(self) -> &'static str {
+        match self {
+            Highlight::Best => "cyan",
+            Highlight::Worst => "gray",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+struct Column {
+    title: String,
+    /// Footnote numbers of the title.
+    notes: &'static [u8],
+    alignment: Alignment,
+    statistics: Vec<Option<Statistic>>,
+    scale: f64,
+    /// See [`round_to_deviation`].
+    rounding: i32,
+    /// Unit written inside the math environment.
+    math_unit: &'static str,
+    /// Unit written after the math environment.
+    text_unit: &'static str,
+    higher_is_better: bool,
+    /// Values above this are the worst ones, instead of the 3 largest.
+    worst_above: Option<f64>,
+}
+
+const HIGHLIGHTED: usize = 3;
+
+impl Column {
+    fn mean(&self, row: usize) -> Option<f64> {
+        self.statistics[row].map(|statistic| statistic.mean() * self.scale)
+    }
+
+    fn highlights(&self) -> Vec<Option<Highlight>> {
+        let mut ranked: Vec<(usize, f64)> = (0..self.statistics.len())
+            .filter_map(|row| Some((row, self.mean(row)?)))
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        if self.higher_is_better {
+            ranked.reverse();
+        }
+
+        let mut highlights = vec![None; self.statistics.len()];
+        match self.worst_above {
+            Some(threshold) => {
+                for &(row, _) in ranked.iter().take(HIGHLIGHTED) {
+                    highlights[row] = Some(Highlight::Best);
+                }
+                for &(row, value) in &ranked {
+                    if value > threshold {
+                        highlights[row] = Some(Highlight::Worst);
+                    }
+                }
+            }
+            None => {
+                for &(row, _) in ranked.iter().rev().take(HIGHLIGHTED) {
+                    highlights[row] = Some(Highlight::Worst);
+                }
+                for &(row, _) in ranked.iter().take(HIGHLIGHTED) {
+                    highlights[row] = Some(Highlight::Best);
+                }
+            }
+        }
+        highlights
+    }
+
+    fn cell(&self, row: usize, highlight: Option<Highlight>, style: TableStyle) -> String {
+        let Some(statistic) = self.statistics[row] else {
+            return String::new();
+        };
+        let mean = statistic.mean() * self.scale;
+        let stddev = statistic.std_dev() * self.scale;
+        let number = match round_to_deviation(mean, stddev, self.rounding) {
+            Some(value) => format!("{value:?}"),
+            None => format!("{mean:.2}"),
+        };
+        let number = number.strip_suffix(".0").unwrap_or(&number);
+        let number = match style {
+            TableStyle::Markdown => number.replace('.', ","),
+            TableStyle::Latex => number.replace('.', "{,}"),
+        };
+        let color = highlight
+            .map(|highlight| format!("\\color{{{}}} ", highlight.color()))
+            .unwrap_or_default();
+        format!("${color}{number}{}${}", self.math_unit, self.text_unit)
+    }
+
+    fn title(&self, style: TableStyle) -> String {
+        const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+        match style {
+            TableStyle::Markdown => self
+                .notes
+                .iter()
+                .map(|&note| SUPERSCRIPTS[note as usize % 10])
+                .fold(self.title.clone(), |mut title, note| {
+                    title.push(note);
+                    title
+                }),
+            TableStyle::Latex if self.notes.is_empty() => self.title.clone(),
+            TableStyle::Latex => {
+                let notes: Vec<String> = self.notes.iter().map(u8::to_string).collect();
+                format!("{}\\textsuperscript{{{}}}", self.title, notes.join(","))
+            }
+        }
+    }
+}
+
+/// Row label of a codec, e.g. `WebP ($q = 90\%$)` or `PNG ($128$ cores)`.
+pub fn label(codec: &CodecSummary) -> String {
+    let parameters = &codec.parameters;
+    if let Some(colors) = parameters.get("colors") {
+        return format!("PNG (${colors}$ cores)");
+    }
+    let family = match codec.family.as_str() {
+        "WEBP" => "WebP",
+        family => family,
+    };
+    let mut details = vec![];
+    if let Some(quality) = parameters.get("quality") {
+        details.push(format!("q = {quality}\\%"));
+    }
+    if let Some(speed) = parameters.get("speed") {
+        if *speed != AvifCodec::DEFAULT_SPEED.to_string() {
+            details.push(format!("s = {speed}"));
+        }
+    }
+    if details.is_empty() {
+        family.to_owned()
+    } else {
+        format!("{family} (${}$)", details.join(", "))
+    }
+}
+
+fn columns(summary: &Summary) -> Vec<Column> {
+    let codecs = &summary.codecs;
+    let mut columns = vec![
+        Column {
+            title: String::from("Tamanho relativo"),
+            notes: &[1, 2],
+            alignment: Alignment::Right,
+            statistics: codecs.iter().map(|c| Some(c.relative_size)).collect(),
+            scale: 1.0,
+            rounding: 0,
+            math_unit: "\\%",
+            text_unit: "",
+            higher_is_better: false,
+            worst_above: Some(100.0),
+        },
+        Column {
+            title: String::from("Tempo médio de codificação"),
+            notes: &[2],
+            alignment: Alignment::Right,
+            statistics: codecs.iter().map(|c| Some(c.time)).collect(),
+            scale: 1e-3,
+            rounding: 0,
+            math_unit: "",
+            text_unit: " ms",
+            higher_is_better: false,
+            worst_above: None,
+        },
+        Column {
+            title: String::from("Tempo médio de decodificação"),
+            notes: &[2],
+            alignment: Alignment::Right,
+            statistics: codecs
+                .iter()
+                .map(|c| (c.decode_time.count() > 0).then_some(c.decode_time))
+                .collect(),
+            scale: 1e-3,
+            rounding: 0,
+            math_unit: "",
+            text_unit: " ms",
+            higher_is_better: false,
+            worst_above: None,
+        },
+    ];
+
+    let registry = metrics::all();
+    let mut names: Vec<&String> = vec![];
+    for codec in codecs {
+        for (name, _) in &codec.metrics {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    for name in names {
+        let metric = registry.iter().find(|metric| &metric.name == name);
+        let higher_is_better = metric.is_some_and(|metric| metric.higher_is_better);
+        let unit = metric.and_then(|metric| metric.unit);
+        let statistics = codecs
+            .iter()
+            .map(|codec| {
+                codec
+                    .metrics
+                    .iter()
+                    .find(|(known, _)| known == name)
+                    .map(|(_, statistic)| *statistic)
+            })
+            .collect();
+        columns.push(Column {
+            title: match unit {
+                Some(unit) => format!("{name} ({unit})"),
+                None => name.clone(),
+            },
+            notes: &[3],
+            alignment: Alignment::Center,
+            statistics,
+            scale: if unit.is_some() { 1.0 } else { 100.0 },
+            rounding: 1,
+            math_unit: "",
+            text_unit: "",
+            higher_is_better,
+            worst_above: None,
+        });
+    }
+    columns
+}
+
+/// Renders the per-codec comparison table.
+pub fn table(summary: &Summary, style: TableStyle) -> String {
+    let columns = columns(summary);
+    let highlights: Vec<Vec<Option<Highlight>>> = columns.iter().map(Column::highlights).collect();
+
+    let mut header = vec![String::from("Formato"), String::from("Com perdas?")];
+    header.extend(columns.iter().map(|column| column.title(style)));
+    let mut alignments = vec![Alignment::Left, Alignment::Center];
+    alignments.extend(columns.iter().map(|column| column.alignment));
+
+    let rows: Vec<Vec<String>> = summary
+        .codecs
+        .iter()
+        .enumerate()
+        .map(|(row, codec)| {
+            let lossy = if codec.lossy { "Sim" } else { "Não" };
+            let mut cells = vec![label(codec), lossy.to_owned()];
+            cells.extend(
+                columns
+                    .iter()
+                    .zip(&highlights)
+                    .map(|(column, highlights)| column.cell(row, highlights[row], style)),
+            );
+            cells
+        })
+        .collect();
+
+    let mut table = String::new();
+    match style {
+        TableStyle::Markdown => {
+            let line = |cells: &[String]| format!("| {} |\n", cells.join(" | "));
+            table += &line(&header);
+            let separators: Vec<String> = alignments
+                .iter()
+                .map(|alignment| match alignment {
+                    Alignment::Left => String::from(":------"),
+                    Alignment::Center => String::from(":---:"),
+                    Alignment::Right => String::from("------:"),
+                })
+                .collect();
+            table += &line(&separators);
+            for row in &rows {
+                table += &line(row);
+            }
+        }
+        TableStyle::Latex => {
+            let line = |cells: &[String]| format!("{} \\\\\n", cells.join(" & "));
+            let spec: String = alignments
+                .iter()
+                .map(|alignment| match alignment {
+                    Alignment::Left => 'l',
+                    Alignment::Center => 'c',
+                    Alignment::Right => 'r',
+                })
+                .collect();
+            table += &format!("\\begin{{tabular}}{{{spec}}}\n\\hline\n");
+            table += &line(&header);
+            table += "\\hline\n";
+            for row in &rows {
+                table += &line(row);
+            }
+            table += "\\hline\n\\end{tabular}\n";
+        }
+    }
+    table
+}
@@ -0,0 +1,634 @@
+//! Typed experiment results and the sinks that persist them.
+//!
+//! Each [`Record`] describes one codec applied to one image. Records are written
+//! either as CSV, with one column per metric (`metric:<name>`) and per hash
+//! (`hash:<name>`), or as JSON Lines.
+use core::{fmt, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+    pub image: PathBuf,
+    pub codec: String,
+    pub family: String,
+    pub lossy: bool,
+    pub parameters: BTreeMap<String, String>,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the original file, in bytes.
+    pub original_size: u64,
+    /// Size of the encoded stream, in bytes.
+    pub stream_size: u64,
+    /// Median encoding time, in μs.
+    pub encode_time_us: u64,
+    /// Median decoding time, in μs.
+    pub decode_time_us: Option<u64>,
+    /// Timed runs per direction, after the warm-up ones.
+    #[serde(default = "one")]
+    pub timing_runs: u32,
+    #[serde(default)]
+    pub encode_time_min_us: Option<u64>,
+    #[serde(default)]
+    pub encode_time_mad_us: Option<u64>,
+    #[serde(default)]
+    pub decode_time_min_us: Option<u64>,
+    #[serde(default)]
+    pub decode_time_mad_us: Option<u64>,
+    /// Whether the decoded image matched the original bit-exactly; [`None`] for lossy codecs.
+    #[serde(default)]
+    pub lossless_verified: Option<bool>,
+    /// Metric values; empty for lossless codecs.
+    #[serde(
+        serialize_with = "serialize_metric_values",
+        deserialize_with = "metric_values"
+    )]
+    pub metrics: BTreeMap<String, f64>,
+    /// Normalized hash distances, in `[0, 1]`; empty for lossless codecs.
+    pub hashes: BTreeMap<String, f64>,
+    /// Part of the dataset split the image belongs to, e.g. `analysis`.
+    #[serde(default)]
+    pub split: Option<String>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// A metric value in JSON, which has no infinity nor NaN: `serde_json` would
+/// write both as `null`, so they are written as strings instead.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonValue {
+    Number(f64),
+    Text(String),
+}
+
+fn serialize_metric_values<S>(
+    values: &BTreeMap<String, f64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_map(values.iter().map(|(name, &value)| {
+        let value = if value.is_finite() {
+            JsonValue::Number(value)
+        } else {
+            JsonValue::Text(value.to_string())
+        };
+        (name, value)
+    }))
+}
+
+/// Metric values serialized as in [`Record`], for JSON written elsewhere.
+pub struct MetricValues<'a>(pub &'a BTreeMap<String, f64>);
+
+impl Serialize for MetricValues<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_metric_values(self.0, serializer)
+    }
+}
+
+/// Reads back the values written by [`serialize_metric_values`].
+fn metric_values<'de, D>(deserializer: D) -> Result<BTreeMap<String, f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values = BTreeMap::<String, JsonValue>::deserialize(deserializer)?;
+    values
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                JsonValue::Number(value) => value,
+                JsonValue::Text(text) => text.parse().map_err(|_| {
+                    serde::de::Error::custom(format!("invalid value {text:?} for metric {name}"))
+                })?,
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+impl Record {
+    /// Stream size relative to the original file, in %.
+    pub fn relative_size(&self) -> f64 {
+        100.0 * self.stream_size as f64 / self.original_size as f64
+    }
+
+    /// Encoding throughput, in MP/s.
+    pub fn encode_throughput(&self) -> f64 {
+        self.throughput(self.encode_time_us)
+    }
+
+    /// Decoding throughput, in MP/s.
+    pub fn decode_throughput(&self) -> Option<f64> {
+        self.decode_time_us.map(|time| self.throughput(time))
+    }
+
+    fn throughput(&self, time_us: u64) -> f64 {
+        // Pixels per μs are megapixels per second
+        (self.width as u64 * self.height as u64) as f64 / time_us.max(1) as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ResultFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ResultFormat {
+    /// Guesses the format from the file extension, defaulting to CSV.
+    pub fn from_path(path: &Path) -> ResultFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl" | "json" | "ndjson") => ResultFormat::Jsonl,
+            _ => ResultFormat::Csv,
+        }
+    }
+}
+
+pub trait RecordSink: Send {
+    fn write(&mut self, record: &Record) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Creates a sink of the given format writing into `path`.
+///
+/// The CSV header is fixed at creation, so the metric and hash names must be known upfront.
+pub fn create_sink(
+    path: &Path,
+    format: ResultFormat,
+    metric_names: Vec<String>,
+    hash_names: Vec<String>,
+) -> io::Result<Box<dyn RecordSink>> {
+    let file = BufWriter::new(File::create(path)?);
+    Ok(match format {
+        ResultFormat::Csv => Box::new(CsvSink::new(file, metric_names, hash_names)?),
+        ResultFormat::Jsonl => Box::new(JsonlSink::new(file)),
+    })
+}
+
+/// Reopens a result file to append to it, returning the records it already holds.
+///
+/// A last row cut short by an interrupted run is dropped. A CSV file must have
+/// been written with the same metric and hash columns. Missing files are created.
+pub fn resume_sink(
+    path: &Path,
+    format: ResultFormat,
+    metric_names: Vec<String>,
+    hash_names: Vec<String>,
+) -> io::Result<(Box<dyn RecordSink>, Vec<Record>)> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok((create_sink(path, format, metric_names, hash_names)?, vec![]));
+        }
+        Err(error) => return Err(error),
+    };
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)?;
+    let complete = contents
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |position| position + 1);
+    file.set_len(complete as u64)?;
+    contents.truncate(complete);
+    if contents.is_empty() {
+        drop(file);
+        return Ok((create_sink(path, format, metric_names, hash_names)?, vec![]));
+    }
+
+    let records = match format {
+        ResultFormat::Csv => {
+            let expected = csv_header(&metric_names, &hash_names);
+            let found = csv::Reader::from_reader(contents.as_slice()).headers()?.clone();
+            if !found.iter().eq(expected.iter().map(String::as_str)) {
+                return Err(invalid_data(format!(
+                    "written with other metrics or hashes, expected the header {}",
+                    expected.join(",")
+                )));
+            }
+            read_csv(contents.as_slice())?
+        }
+        ResultFormat::Jsonl => read_jsonl(contents.as_slice())?,
+    };
+
+    file.seek(io::SeekFrom::End(0))?;
+    let file = BufWriter::new(file);
+    let sink: Box<dyn RecordSink> = match format {
+        ResultFormat::Csv => Box::new(CsvSink::appending(file, metric_names, hash_names)),
+        ResultFormat::Jsonl => Box::new(JsonlSink::new(file)),
+    };
+    Ok((sink, records))
+}
+
+const CSV_COLUMNS: [&str; 18] = [
+    "image",
+    "codec",
+    "family",
+    "lossy",
+    "parameters",
+    "width",
+    "height",
+    "original_size",
+    "stream_size",
+    "encode_time_us",
+    "decode_time_us",
+    "timing_runs",
+    "encode_time_min_us",
+    "encode_time_mad_us",
+    "decode_time_min_us",
+    "decode_time_mad_us",
+    "lossless_verified",
+    "split",
+];
+const METRIC_PREFIX: &str = "metric:";
+const HASH_PREFIX: &str = "hash:";
+
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+    metric_names: Vec<String>,
+    hash_names: Vec<String>,
+}
+
+fn csv_header(metric_names: &[String], hash_names: &[String]) -> Vec<String> {
+    CSV_COLUMNS
+        .iter()
+        .map(|&column| column.to_owned())
+        .chain(metric_names.iter().map(|name| format!("{METRIC_PREFIX}{name}")))
+        .chain(hash_names.iter().map(|name| format!("{HASH_PREFIX}{name}")))
+        .collect()
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W, metric_names: Vec<String>, hash_names: Vec<String>) -> io::Result<Self> {
+        let mut sink = CsvSink::appending(writer, metric_names, hash_names);
+        sink.writer
+            .write_record(csv_header(&sink.metric_names, &sink.hash_names))?;
+        Ok(sink)
+    }
+
+    /// A sink continuing a file that already has its header.
+    pub fn appending(writer: W, metric_names: Vec<String>, hash_names: Vec<String>) -> Self {
+        CsvSink {
+            writer: csv::Writer::from_writer(writer),
+            metric_names,
+            hash_names,
+        }
+    }
+}
+
+fn optional<T: ToString>(value: Option<&T>) -> String {
+    value.map(T::to_string).unwrap_or_default()
+}
+
+impl<W: Write + Send> RecordSink for CsvSink<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let parameters = record
+            .parameters
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(";");
+        let row = [
+            record.image.display().to_string(),
+            record.codec.clone(),
+            record.family.clone(),
+            record.lossy.to_string(),
+            parameters,
+            record.width.to_string(),
+            record.height.to_string(),
+            record.original_size.to_string(),
+            record.stream_size.to_string(),
+            record.encode_time_us.to_string(),
+            optional(record.decode_time_us.as_ref()),
+            record.timing_runs.to_string(),
+            optional(record.encode_time_min_us.as_ref()),
+            optional(record.encode_time_mad_us.as_ref()),
+            optional(record.decode_time_min_us.as_ref()),
+            optional(record.decode_time_mad_us.as_ref()),
+            optional(record.lossless_verified.as_ref()),
+            optional(record.split.as_ref()),
+        ]
+        .into_iter()
+        .chain(
+            self.metric_names
+                .iter()
+                .map(|name| optional(record.metrics.get(name))),
+        )
+        .chain(
+            self.hash_names
+                .iter()
+                .map(|name| optional(record.hashes.get(name))),
+        );
+        self.writer.write_record(row)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct JsonlSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlSink { writer }
+    }
+}
+
+impl<W: Write + Send> RecordSink for JsonlSink<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads every record of a result file written by one of the sinks.
+pub fn read_records(path: &Path, format: ResultFormat) -> io::Result<Vec<Record>> {
+    let file = BufReader::new(File::open(path)?);
+    match format {
+        ResultFormat::Csv => read_csv(file),
+        ResultFormat::Jsonl => read_jsonl(file),
+    }
+}
+
+fn read_jsonl(reader: impl BufRead) -> io::Result<Vec<Record>> {
+    reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+fn read_csv(reader: impl io::Read) -> io::Result<Vec<Record>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let header = reader.headers()?.clone();
+    if header.len() < CSV_COLUMNS.len()
+        || header.iter().zip(CSV_COLUMNS).any(|(found, expected)| found != expected)
+    {
+        return Err(invalid_data(format!(
+            "unexpected CSV header, expected it to start with {}",
+            CSV_COLUMNS.join(",")
+        )));
+    }
+
+    let mut records = vec![];
+    for row in reader.records() {
+        let row = row?;
+        let field = |index: usize| row.get(index).unwrap_or_default();
+        fn parse<T: FromStr>(column: &str, value: &str) -> io::Result<T> {
+            value
+                .parse()
+                .map_err(|_| invalid_data(format!("invalid value {value:?} in column {column}")))
+        }
+
+        let parameters = field(4)
+            .split(';')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (key.to_owned(), value.to_owned())
+            })
+            .collect();
+        fn optional_parse<T: FromStr>(column: &str, value: &str) -> io::Result<Option<T>> {
+            match value {
+                "" => Ok(None),
+                value => Ok(Some(parse(column, value)?)),
+            }
+        }
+        let mut record = Record {
+            image: PathBuf::from(field(0)),
+            codec: field(1).to_owned(),
+            family: field(2).to_owned(),
+            lossy: parse(CSV_COLUMNS[3], field(3))?,
+            parameters,
+            width: parse(CSV_COLUMNS[5], field(5))?,
+            height: parse(CSV_COLUMNS[6], field(6))?,
+            original_size: parse(CSV_COLUMNS[7], field(7))?,
+            stream_size: parse(CSV_COLUMNS[8], field(8))?,
+            encode_time_us: parse(CSV_COLUMNS[9], field(9))?,
+            decode_time_us: optional_parse(CSV_COLUMNS[10], field(10))?,
+            timing_runs: parse(CSV_COLUMNS[11], field(11))?,
+            encode_time_min_us: optional_parse(CSV_COLUMNS[12], field(12))?,
+            encode_time_mad_us: optional_parse(CSV_COLUMNS[13], field(13))?,
+            decode_time_min_us: optional_parse(CSV_COLUMNS[14], field(14))?,
+            decode_time_mad_us: optional_parse(CSV_COLUMNS[15], field(15))?,
+            lossless_verified: optional_parse(CSV_COLUMNS[16], field(16))?,
+            metrics: BTreeMap::new(),
+            hashes: BTreeMap::new(),
+            split: optional_parse(CSV_COLUMNS[17], field(17))?,
+        };
+        for (column, value) in header.iter().zip(row.iter()).skip(CSV_COLUMNS.len()) {
+            if value.is_empty() {
+                continue;
+            }
+            if let Some(name) = column.strip_prefix(METRIC_PREFIX) {
+                record.metrics.insert(name.to_owned(), parse(column, value)?);
+            } else if let Some(name) = column.strip_prefix(HASH_PREFIX) {
+                record.hashes.insert(name.to_owned(), parse(column, value)?);
+            }
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// How a results file was produced, written next to it so the run can be reproduced.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RunMetadata {
+    pub version: String,
+    pub dataset: PathBuf,
+    pub manifest: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    /// Images selected for the run, after sampling.
+    pub images: usize,
+    pub seed: u64,
+    pub sample: Option<String>,
+    pub stratified_sample: bool,
+    pub split: Option<String>,
+    pub shard: Option<String>,
+    pub repeat: usize,
+    pub warmup: usize,
+    pub codecs: Vec<String>,
+    pub metrics: Vec<String>,
+    pub hashes: Vec<String>,
+    /// Codecs that can not encode an image, e.g. QOI for greyscale ones, which
+    /// a resumed run skips as if they were done.
+    #[serde(default)]
+    pub unsupported: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// The runs this one resumed, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub earlier: Vec<RunMetadata>,
+}
+
+impl RunMetadata {
+    /// Where the metadata of a results file goes, e.g. `results.meta.json` for `results.csv`.
+    pub fn path_for(results: &Path) -> PathBuf {
+        results.with_extension("meta.json")
+    }
+
+    pub fn read(path: &Path) -> io::Result<RunMetadata> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Carries over what `previous` ran and found unsupported, for a run
+    /// resuming it.
+    pub fn resume(&mut self, mut previous: RunMetadata) {
+        self.earlier = core::mem::take(&mut previous.earlier);
+        self.unsupported = core::mem::take(&mut previous.unsupported);
+        self.earlier.push(previous);
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)?;
+        file.flush()
+    }
+}
+
+/// An (image, codec) pair found in more than one result file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+    pub image: PathBuf,
+    pub codec: String,
+    pub first: PathBuf,
+    pub second: PathBuf,
+}
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}]: in {} and {}",
+            self.image.display(),
+            self.codec,
+            self.first.display(),
+            self.second.display()
+        )
+    }
+}
+
+/// Concatenates the records of several result files, keeping the first of
+/// every duplicated (image, codec) pair.
+pub fn merge(sources: Vec<(PathBuf, Vec<Record>)>) -> (Vec<Record>, Vec<Duplicate>) {
+    let mut origins: HashMap<(PathBuf, String), PathBuf> = HashMap::new();
+    let mut merged = vec![];
+    let mut duplicates = vec![];
+    for (source, records) in sources {
+        for record in records {
+            let key = (record.image.clone(), record.codec.clone());
+            match origins.get(&key) {
+                Some(first) => duplicates.push(Duplicate {
+                    image: key.0,
+                    codec: key.1,
+                    first: first.clone(),
+                    second: source.clone(),
+                }),
+                None => {
+                    origins.insert(key, source.clone());
+                    merged.push(record);
+                }
+            }
+        }
+    }
+    (merged, duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(metrics: &[(&str, f64)]) -> Record {
+        Record {
+            image: PathBuf::from("a.png"),
+            codec: String::from("JPEG"),
+            family: String::from("JPEG"),
+            lossy: true,
+            parameters: BTreeMap::new(),
+            width: 1,
+            height: 1,
+            original_size: 1,
+            stream_size: 1,
+            encode_time_us: 1,
+            decode_time_us: None,
+            timing_runs: 1,
+            encode_time_min_us: None,
+            encode_time_mad_us: None,
+            decode_time_min_us: None,
+            decode_time_mad_us: None,
+            lossless_verified: None,
+            metrics: metrics.iter().map(|&(name, value)| (name.to_owned(), value)).collect(),
+            hashes: BTreeMap::new(),
+            split: None,
+        }
+    }
+
+    #[test]
+    fn jsonl_keeps_non_finite_metrics_apart() {
+        let written = record(&[("PSNR", f64::INFINITY), ("SSIM", f64::NAN), ("MSE", 0.5)]);
+        let mut buffer = vec![];
+        JsonlSink::new(&mut buffer).write(&written).unwrap();
+        let read = read_jsonl(buffer.as_slice()).unwrap().remove(0);
+        assert_eq!(read.metrics["PSNR"], f64::INFINITY);
+        assert!(read.metrics["SSIM"].is_nan());
+        assert_eq!(read.metrics["MSE"], 0.5);
+    }
+
+    fn metadata(seed: u64) -> RunMetadata {
+        RunMetadata {
+            version: String::from("0.1.0"),
+            dataset: PathBuf::from("dataset"),
+            manifest: None,
+            config: None,
+            images: 2,
+            seed,
+            sample: None,
+            stratified_sample: false,
+            split: None,
+            shard: None,
+            repeat: 1,
+            warmup: 0,
+            codecs: vec![String::from("QOI")],
+            metrics: vec![],
+            hashes: vec![],
+            unsupported: BTreeMap::new(),
+            earlier: vec![],
+        }
+    }
+
+    #[test]
+    fn resuming_keeps_earlier_runs_and_unsupported_pairs() {
+        let mut first = metadata(1);
+        first.unsupported.entry(PathBuf::from("gray.png")).or_default().insert("QOI".into());
+        let mut second = metadata(2);
+        second.resume(first);
+        let mut third = metadata(3);
+        third.resume(second);
+
+        let seeds: Vec<u64> = third.earlier.iter().map(|run| run.seed).collect();
+        assert_eq!(seeds, [1, 2]);
+        assert!(third.earlier.iter().all(|run| run.earlier.is_empty()));
+        assert!(third.unsupported[Path::new("gray.png")].contains("QOI"));
+    }
+}
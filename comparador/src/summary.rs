@@ -0,0 +1,356 @@
+//! Per-codec aggregation of result records, as previously done by `parse-logs.py`.
+use crate::{metrics, metrics::hash, results::Record};
+
+use core::fmt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Streaming min / mean / variance / max, using Welford's algorithm.
+#[derive(Clone, Copy, Debug)]
+pub struct Statistic {
+    count: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Default for Statistic {
+    fn default() -> Self {
+        Statistic {
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+}
+
+impl Statistic {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Combines two partial statistics (Chan et al.).
+    pub fn merge(&mut self, other: &Statistic) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta.powi(2) * (self.count * other.count) as f64 / count as f64;
+        self.count = count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+    /// Population variance.
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+/// How a [`Statistic`] is printed: unit, scale and significant digits of the deviation.
+#[derive(Clone, Copy, Debug)]
+pub struct StatisticFormat {
+    pub unit: &'static str,
+    pub scale: f64,
+    /// Decimal places kept after the most significant digit of the standard deviation.
+    pub rounding: i32,
+    pub span: bool,
+}
+
+impl StatisticFormat {
+    pub const FILESIZE: StatisticFormat = StatisticFormat {
+        unit: "KiB",
+        scale: 1e-3,
+        rounding: 0,
+        span: false,
+    };
+    pub const RELATIVE_SIZE: StatisticFormat = StatisticFormat {
+        unit: "%",
+        scale: 1.0,
+        rounding: 1,
+        span: true,
+    };
+    pub const TIME: StatisticFormat = StatisticFormat {
+        unit: "ms",
+        scale: 1e-3,
+        rounding: 1,
+        span: true,
+    };
+    pub const THROUGHPUT: StatisticFormat = StatisticFormat {
+        unit: "MP/s",
+        scale: 1.0,
+        rounding: 1,
+        span: true,
+    };
+    pub const PERCENTAGE: StatisticFormat = StatisticFormat {
+        unit: "%",
+        scale: 100.0,
+        rounding: 1,
+        span: true,
+    };
+
+    /// Formats a statistic as `min | mean ± stddev | max`.
+    pub fn format(&self, statistic: &Statistic) -> String {
+        let scale = self.scale;
+        let (min, mean, stddev, max) = (
+            statistic.min() * scale,
+            statistic.mean() * scale,
+            statistic.std_dev() * scale,
+            statistic.max() * scale,
+        );
+        let number = |value: f64| -> String {
+            match round_to_deviation(value, stddev, self.rounding) {
+                Some(value) => format!("{value:?}"),
+                None => format!("{value:.2}"),
+            }
+        };
+        if self.span {
+            format!(
+                "{} | {} ± {} | {}",
+                number(min),
+                number(mean),
+                number(stddev),
+                number(max)
+            )
+        } else {
+            format!("{} ± {}", number(mean), number(stddev))
+        }
+    }
+}
+
+/// Rounds `value` to `rounding` decimal places after the most significant digit of `stddev`.
+///
+/// Returns [`None`] when there is no deviation to round to.
+pub fn round_to_deviation(value: f64, stddev: f64, rounding: i32) -> Option<f64> {
+    if stddev > 0.0 {
+        let digits = rounding - stddev.log10().floor() as i32;
+        let factor = 10f64.powi(digits);
+        Some((value * factor).round() / factor)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CodecSummary {
+    pub name: String,
+    pub family: String,
+    pub parameters: BTreeMap<String, String>,
+    pub lossy: bool,
+    /// Stream size, in bytes.
+    pub filesize: Statistic,
+    /// Stream size relative to the original file, in %.
+    pub relative_size: Statistic,
+    /// Encoding time, in μs.
+    pub time: Statistic,
+    /// Decoding time, in μs.
+    pub decode_time: Statistic,
+    /// Encoding throughput, in MP/s.
+    pub encode_throughput: Statistic,
+    /// Decoding throughput, in MP/s.
+    pub decode_throughput: Statistic,
+    /// Lossless round-trips checked, and how many of them matched the original.
+    pub lossless_checked: u64,
+    pub lossless_verified: u64,
+    pub hashes: Vec<(String, Statistic)>,
+    pub metrics: Vec<(String, Statistic)>,
+}
+
+impl CodecSummary {
+    fn new(record: &Record) -> Self {
+        CodecSummary {
+            name: record.codec.clone(),
+            family: record.family.clone(),
+            parameters: record.parameters.clone(),
+            lossy: record.lossy,
+            filesize: Statistic::default(),
+            relative_size: Statistic::default(),
+            time: Statistic::default(),
+            decode_time: Statistic::default(),
+            encode_throughput: Statistic::default(),
+            decode_throughput: Statistic::default(),
+            lossless_checked: 0,
+            lossless_verified: 0,
+            hashes: vec![],
+            metrics: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub total_images: usize,
+    /// Codecs in the order they first appear in the results.
+    pub codecs: Vec<CodecSummary>,
+}
+
+/// Pushes `values` into `statistics`, keeping them in the order of `registry`,
+/// then by name for anything unknown to it. Non-finite values, such as the
+/// infinite PSNR of identical images, are left out.
+fn accumulate<'a>(
+    statistics: &mut Vec<(String, Statistic)>,
+    values: impl Iterator<Item = (&'a String, &'a f64)>,
+    registry: &[String],
+    ignore_zeroes: bool,
+) {
+    for (name, &value) in values {
+        if (ignore_zeroes && value == 0.0) || !value.is_finite() {
+            continue;
+        }
+        match statistics.iter_mut().find(|(known, _)| known == name) {
+            Some((_, statistic)) => statistic.push(value),
+            None => {
+                let mut statistic = Statistic::default();
+                statistic.push(value);
+                statistics.push((name.clone(), statistic));
+                let position = |name: &String| {
+                    (
+                        registry.iter().position(|known| known == name).unwrap_or(usize::MAX),
+                        name.clone(),
+                    )
+                };
+                statistics.sort_by_key(|(name, _)| position(name));
+            }
+        }
+    }
+}
+
+/// Aggregates the records per codec.
+///
+/// With `ignore_zeroes`, null hash distances and metric values are left out,
+/// as with `parse-logs.py -i`.
+pub fn summarize(records: impl IntoIterator<Item = Record>, ignore_zeroes: bool) -> Summary {
+    let metric_names: Vec<String> = metrics::all().into_iter().map(|m| m.name).collect();
+    let hash_names: Vec<String> = hash::all().into_iter().map(|h| h.name).collect();
+
+    let mut images = HashSet::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut summary = Summary::default();
+    for record in records {
+        let index = *positions.entry(record.codec.clone()).or_insert_with(|| {
+            summary.codecs.push(CodecSummary::new(&record));
+            summary.codecs.len() - 1
+        });
+        let codec = &mut summary.codecs[index];
+
+        codec.filesize.push(record.stream_size as f64);
+        codec.relative_size.push(record.relative_size());
+        codec.time.push(record.encode_time_us as f64);
+        codec.encode_throughput.push(record.encode_throughput());
+        if let Some(verified) = record.lossless_verified {
+            codec.lossless_checked += 1;
+            codec.lossless_verified += verified as u64;
+        }
+        if let (Some(time), Some(throughput)) = (record.decode_time_us, record.decode_throughput()) {
+            codec.decode_time.push(time as f64);
+            codec.decode_throughput.push(throughput);
+        }
+        accumulate(
+            &mut codec.hashes,
+            record.hashes.iter(),
+            &hash_names,
+            ignore_zeroes,
+        );
+        accumulate(
+            &mut codec.metrics,
+            record.metrics.iter(),
+            &metric_names,
+            ignore_zeroes,
+        );
+        images.insert(record.image);
+    }
+    summary.total_images = images.len();
+    summary
+}
+
+fn write_line(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    display: StatisticFormat,
+    statistic: &Statistic,
+) -> fmt::Result {
+    let name = format!("{name} ({})", display.unit);
+    writeln!(f, "- {name:<10}: {}", display.format(statistic))
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units: HashMap<String, &'static str> = metrics::all()
+            .into_iter()
+            .filter_map(|metric| Some((metric.name, metric.unit?)))
+            .collect();
+        writeln!(f, "Total files: {}", self.total_images)?;
+        for codec in &self.codecs {
+            let kind = if codec.lossy { "Lossy" } else { "Lossless" };
+            writeln!(f, "Per image statistics for codec {} ({kind})", codec.name)?;
+            write_line(f, "Filesize", StatisticFormat::FILESIZE, &codec.filesize)?;
+            write_line(f, "Relative Size", StatisticFormat::RELATIVE_SIZE, &codec.relative_size)?;
+            write_line(f, "Time", StatisticFormat::TIME, &codec.time)?;
+            write_line(f, "Throughput", StatisticFormat::THROUGHPUT, &codec.encode_throughput)?;
+            if codec.decode_time.count() > 0 {
+                write_line(f, "Decode Time", StatisticFormat::TIME, &codec.decode_time)?;
+                write_line(
+                    f,
+                    "Decode Throughput",
+                    StatisticFormat::THROUGHPUT,
+                    &codec.decode_throughput,
+                )?;
+            }
+            if codec.lossless_checked > 0 {
+                writeln!(
+                    f,
+                    "- Verified round-trips: {}/{}",
+                    codec.lossless_verified, codec.lossless_checked
+                )?;
+            }
+            if codec.lossy {
+                writeln!(f, "Hashes:")?;
+                for (name, statistic) in &codec.hashes {
+                    write_line(f, name, StatisticFormat::PERCENTAGE, statistic)?;
+                }
+                writeln!(f, "Metrics:")?;
+                for (name, statistic) in &codec.metrics {
+                    let display = match units.get(name) {
+                        Some(&unit) => StatisticFormat {
+                            unit,
+                            scale: 1.0,
+                            ..StatisticFormat::PERCENTAGE
+                        },
+                        None => StatisticFormat::PERCENTAGE,
+                    };
+                    write_line(f, name, display, statistic)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -51,6 +51,7 @@ where
         + a[2][2] * b[2][2]
 }
 
+#[allow(clippy::needless_range_loop)]
 pub fn dct<const N: usize, const M: usize>(array: [[u8; N]; N]) -> [[f64; M]; M] {
     use core::f64::consts::PI;
 
@@ -82,11 +83,16 @@ pub fn dct<const N: usize, const M: usize>(array: [[u8; N]; N]) -> [[f64; M]; M]
 }
 
 /// https://arxiv.org/pdf/1308.3052
+///
+/// Empty for images under 6×6, whose halves are smaller than the 3×3 kernel.
 pub fn gradient_magnitude_similarity(original: &DynamicImage, other: &DynamicImage) -> Vec<f64> {
     const C: f64 = 0.0026;
 
     let nwidth = original.width() >> 1;
     let nheight = original.height() >> 1;
+    if nwidth < 3 || nheight < 3 {
+        return vec![];
+    }
     let filter = FilterType::Triangle;
     let original = original.resize_exact(nwidth, nheight, filter).to_luma16();
     let other = other.resize_exact(nwidth, nheight, filter).to_luma16();